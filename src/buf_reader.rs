@@ -0,0 +1,118 @@
+//! 带内部缓冲区的接收适配器，实现 `embedded-io` 的 `BufRead`，需要启用 `embedded-io` feature。
+//!
+//! 行解析等协议代码可以先 `fill_buf` 查看数据，只 `consume` 真正用掉的部分。
+
+use embedded_io::{BufRead, ErrorKind, ErrorType, Read};
+
+use crate::{
+    pl011::{ReadError, Uart},
+    wake::{DefaultWakeSource, WakeSource},
+};
+
+impl embedded_io::Error for ReadError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ReadError::Overrun => ErrorKind::Other,
+            ReadError::Parity(_) | ReadError::Framing | ReadError::Break => ErrorKind::InvalidData,
+        }
+    }
+}
+
+/// 在 `Uart` 的接收路径上加一层 `N` 字节的缓冲区
+///
+/// 缓冲区读空后，`fill_buf` 忙等到接收 FIFO 有数据，再把 FIFO 中的数据尽量读入缓冲区。
+/// 带错误标志的字节不进入缓冲区，错误在缓冲区中已有的数据取完后返回。
+pub struct UartBufReader<
+    'a,
+    const N: usize,
+    const DEPTH: usize = 16,
+    W: WakeSource = DefaultWakeSource,
+> {
+    uart: &'a Uart<DEPTH, W>,
+    buf: [u8; N],
+    pos: usize,
+    filled: usize,
+    // 填充缓冲区时遇到、尚未返回的错误
+    error: Option<ReadError>,
+}
+
+impl<'a, const N: usize, const DEPTH: usize, W: WakeSource> UartBufReader<'a, N, DEPTH, W> {
+    /// `N` 必须大于 0，否则无法读入任何数据，编译时报错。
+    pub fn new(uart: &'a Uart<DEPTH, W>) -> Self {
+        const { assert!(N > 0, "UartBufReader needs a non-empty buffer") };
+        Self {
+            uart,
+            buf: [0; N],
+            pos: 0,
+            filled: 0,
+            error: None,
+        }
+    }
+
+    /// 缓冲区中还没有被 `consume` 的字节数
+    pub fn buffered(&self) -> usize {
+        self.filled - self.pos
+    }
+
+    // 缓冲区为空时从接收 FIFO 补充数据，至少读到一个字节或一个错误
+    fn refill(&mut self) -> Result<(), ReadError> {
+        if self.pos < self.filled {
+            return Ok(());
+        }
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.pos = 0;
+        self.filled = 0;
+        while self.filled == 0 {
+            // 等待期间调用 `set_spin_hook` 设置的钩子，超过自旋上限后继续等待
+            while !self.uart.spin_until(|| self.uart.has_rx_data()) {}
+            while self.filled < N {
+                match self.uart.receive_checked() {
+                    Some(Ok(byte)) => {
+                        self.buf[self.filled] = byte;
+                        self.filled += 1;
+                    }
+                    // 还没有数据时直接返回错误，否则先交付已读到的数据
+                    Some(Err(err)) if self.filled == 0 => return Err(err),
+                    Some(Err(err)) => {
+                        self.error = Some(err);
+                        break;
+                    }
+                    None => break,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize, const DEPTH: usize, W: WakeSource> ErrorType
+    for UartBufReader<'_, N, DEPTH, W>
+{
+    type Error = ReadError;
+}
+
+impl<const N: usize, const DEPTH: usize, W: WakeSource> BufRead for UartBufReader<'_, N, DEPTH, W> {
+    fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        self.refill()?;
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+impl<const N: usize, const DEPTH: usize, W: WakeSource> Read for UartBufReader<'_, N, DEPTH, W> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let data = self.fill_buf()?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
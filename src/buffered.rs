@@ -0,0 +1,211 @@
+//! 带软件收发缓冲区的 `Uart`，中断处理在硬件 FIFO 与缓冲区之间搬运数据。
+
+use kspin::SpinNoIrq;
+use tock_registers::interfaces::{Readable, Writeable};
+
+use crate::{
+    pl011::{TimedOut, Uart},
+    regs::{DATA, FLAG},
+    wake::{DefaultWakeSource, WakeSource},
+};
+
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+// 固定容量的字节环形缓冲区
+struct RingBuf<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuf<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.buf[(self.head + self.len) % N] = byte;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+// XON/XOFF 软件流控状态
+struct SwFlow {
+    enabled: bool,
+    high: usize,
+    low: usize,
+    // 已向对端发送 XOFF，尚未发送 XON
+    paused: bool,
+    // 等待插队发送的控制字符
+    pending: Option<u8>,
+}
+
+struct State<const N: usize> {
+    rx: RingBuf<N>,
+    tx: RingBuf<N>,
+    flow: SwFlow,
+}
+
+/// 带 `N` 字节收发缓冲区的 UART。
+///
+/// 需要使能接收、接收超时和发送中断，并在中断处理中调用 `handle_interrupt`。
+pub struct BufferedUart<
+    const N: usize = 256,
+    const DEPTH: usize = 16,
+    W: WakeSource = DefaultWakeSource,
+> {
+    uart: Uart<DEPTH, W>,
+    state: SpinNoIrq<State<N>>,
+}
+
+impl<const N: usize, const DEPTH: usize, W: WakeSource> BufferedUart<N, DEPTH, W> {
+    pub const fn new(uart: Uart<DEPTH, W>) -> Self {
+        Self {
+            uart,
+            state: SpinNoIrq::new(State {
+                rx: RingBuf::new(),
+                tx: RingBuf::new(),
+                flow: SwFlow {
+                    enabled: false,
+                    high: N,
+                    low: 0,
+                    paused: false,
+                    pending: None,
+                },
+            }),
+        }
+    }
+
+    /// 底层的 `Uart`
+    pub fn uart(&self) -> &Uart<DEPTH, W> {
+        &self.uart
+    }
+
+    /// 开关 XON/XOFF 软件流控。
+    ///
+    /// 接收缓冲区中的数据达到 `high` 字节时向对端发送 XOFF，读取后降到 `low` 字节以下时
+    /// 发送 XON。控制字符插在已排队的发送数据之前发出。
+    pub fn set_sw_flow_control(&self, enable: bool, high: usize, low: usize) {
+        let mut state = self.state.lock();
+        state.flow.enabled = enable;
+        state.flow.high = high.min(N);
+        state.flow.low = low.min(high);
+        if !enable && state.flow.paused {
+            state.flow.paused = false;
+            state.flow.pending = Some(XON);
+        }
+        self.pump_tx(&mut state);
+    }
+
+    /// 把 `data` 放入发送缓冲区，返回放入的字节数，缓冲区满时可能只放入一部分。
+    pub fn write(&self, data: &[u8]) -> usize {
+        let mut state = self.state.lock();
+        let count = data.iter().take_while(|&&b| state.tx.push(b)).count();
+        self.pump_tx(&mut state);
+        count
+    }
+
+    /// 不等待发送地把 `data` 放入发送队列，语义同 `write`。
+    ///
+    /// 队列中的数据由 `handle_interrupt` 在发送中断时填入发送 FIFO。返回值小于
+    /// `data.len()` 表示队列已满，调用者可以据此减慢输出或丢弃剩余数据。
+    pub fn enqueue(&self, data: &[u8]) -> usize {
+        self.write(data)
+    }
+
+    /// 发送队列的剩余空间（字节）。
+    pub fn tx_free(&self) -> usize {
+        N - self.state.lock().tx.len
+    }
+
+    /// 从接收缓冲区读取数据，返回读取的字节数，没有数据时返回 0。
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        let mut state = self.state.lock();
+        let mut count = 0;
+        for slot in buf.iter_mut() {
+            let Some(byte) = state.rx.pop() else {
+                break;
+            };
+            *slot = byte;
+            count += 1;
+        }
+        if state.flow.enabled && state.flow.paused && state.rx.len < state.flow.low {
+            state.flow.paused = false;
+            state.flow.pending = Some(XON);
+            self.pump_tx(&mut state);
+        }
+        count
+    }
+
+    /// 发完软件发送队列中的数据后关闭 UART。
+    ///
+    /// `Uart::disable` 看不到这里的发送队列，直接调用会丢弃排队中的数据。这里先把队列
+    /// （包括待插队的 XON/XOFF）全部填入发送 FIFO，再由 `Uart::disable` 等待 `TXFE`
+    /// 和 `BUSY` 后关闭 UART。每一步的等待都受自旋上限约束，任何一步超时仍会关闭 UART，
+    /// 并返回 `TimedOut`，此时队列中可能还有没有发出的数据。
+    pub fn disable(&self) -> Result<(), TimedOut> {
+        let queued = self.uart.spin_until(|| {
+            let mut state = self.state.lock();
+            self.pump_tx(&mut state);
+            state.tx.len == 0 && state.flow.pending.is_none()
+        });
+        let disabled = self.uart.disable();
+        if queued { disabled } else { Err(TimedOut) }
+    }
+
+    /// 中断处理：把接收 FIFO 中的数据移入接收缓冲区，把发送缓冲区的数据填入发送 FIFO。
+    ///
+    /// 接收缓冲区满时多余的字节被丢弃。
+    pub fn handle_interrupt(&self) {
+        // 先清除中断再搬运数据，搬运期间到达的数据会重新触发中断
+        self.uart.handle_interrupt();
+        let mut state = self.state.lock();
+        while let Some(byte) = self.uart.try_receive() {
+            state.rx.push(byte);
+        }
+        if state.flow.enabled && !state.flow.paused && state.rx.len >= state.flow.high {
+            state.flow.paused = true;
+            state.flow.pending = Some(XOFF);
+        }
+        self.pump_tx(&mut state);
+    }
+
+    // 先发送待插队的控制字符，再发送缓冲区中的数据，直到发送 FIFO 满
+    fn pump_tx(&self, state: &mut State<N>) {
+        let regs = unsafe { self.uart.base.as_ref() };
+        if let Some(ctrl) = state.flow.pending {
+            if regs.uartfr.is_set(FLAG::TXFF) {
+                return;
+            }
+            regs.uartdr.write(DATA::RAW.val(ctrl as u32));
+            self.uart.note_tx(1);
+            state.flow.pending = None;
+        }
+        while !regs.uartfr.is_set(FLAG::TXFF) {
+            let Some(byte) = state.tx.pop() else {
+                break;
+            };
+            regs.uartdr.write(DATA::RAW.val(byte as u32));
+            self.uart.note_tx(1);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! COBS 帧编解码，需要启用 `cobs` feature。
+//!
+//! 每帧编码后以一个 0 字节结尾，接收方可以在任意位置通过 0 字节重新对齐帧边界。
+
+use crate::{
+    pl011::{ReadError, Uart},
+    wake::WakeSource,
+};
+
+/// COBS 帧读取错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CobsError {
+    /// 底层接收错误
+    Read(ReadError),
+    /// 解码后的帧超过缓冲区长度，该帧已被读完丢弃
+    FrameTooLong,
+    /// 帧在编码块结束前遇到分隔符
+    Malformed,
+}
+
+impl From<ReadError> for CobsError {
+    fn from(err: ReadError) -> Self {
+        Self::Read(err)
+    }
+}
+
+// 一个编码块最多携带的非零字节数
+const MAX_RUN: usize = 254;
+
+impl<const DEPTH: usize, W: WakeSource> Uart<DEPTH, W> {
+    /// COBS 编码 `payload` 后发送，并追加 0 分隔符。
+    ///
+    /// 边编码边发送，不需要额外的缓冲区。
+    pub async fn write_cobs_frame(&mut self, payload: &[u8]) {
+        let mut rest = payload;
+        loop {
+            match rest.iter().take(MAX_RUN).position(|&b| b == 0) {
+                // 0 字节由编码块隐含，跳过它
+                Some(n) => {
+                    self.write(&[n as u8 + 1]).await;
+                    self.write(&rest[..n]).await;
+                    rest = &rest[n + 1..];
+                }
+                None => {
+                    let n = rest.len().min(MAX_RUN);
+                    self.write(&[n as u8 + 1]).await;
+                    self.write(&rest[..n]).await;
+                    rest = &rest[n..];
+                    if n < MAX_RUN || rest.is_empty() {
+                        break;
+                    }
+                }
+            }
+        }
+        self.write(&[0]).await;
+    }
+
+    /// 接收一帧直到 0 分隔符，解码到 `buf`，返回解码后的长度。
+    ///
+    /// 分隔符之前的空帧会被跳过。帧超过 `buf` 时继续读到分隔符以保持帧对齐，
+    /// 然后返回 `CobsError::FrameTooLong`。
+    pub async fn read_cobs_frame(&mut self, buf: &mut [u8]) -> Result<usize, CobsError> {
+        let mut code = 0;
+        while code == 0 {
+            code = self.read_byte().await?;
+        }
+        let mut remaining = code - 1;
+        let mut len = 0;
+        let mut overflow = false;
+        loop {
+            let byte = self.read_byte().await?;
+            if byte == 0 {
+                if remaining != 0 {
+                    return Err(CobsError::Malformed);
+                }
+                if overflow {
+                    return Err(CobsError::FrameTooLong);
+                }
+                return Ok(len);
+            }
+            let data = if remaining == 0 {
+                // 新编码块开始，上一块不满时隐含一个 0 字节
+                let implied_zero = code != 0xFF;
+                code = byte;
+                remaining = code - 1;
+                if !implied_zero {
+                    continue;
+                }
+                0
+            } else {
+                remaining -= 1;
+                byte
+            };
+            match buf.get_mut(len) {
+                Some(slot) => {
+                    *slot = data;
+                    len += 1;
+                }
+                None => overflow = true,
+            }
+        }
+    }
+
+    async fn read_byte(&mut self) -> Result<u8, ReadError> {
+        let mut byte = [0];
+        self.read(&mut byte).await?;
+        Ok(byte[0])
+    }
+}
@@ -0,0 +1,94 @@
+//! crate 统一的错误类型。
+//!
+//! 各个方法仍然返回各自具体的错误类型，它们都可以通过 `From` 转换为 `Error`，
+//! 在同一个函数中组合多个方法时可以直接用 `?`。
+
+use crate::pl011::{
+    InitError, InvalidBaud, MapError, ReadError, RecoverError, TimedOut, WriteError,
+};
+
+/// 驱动的所有错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// UART 未使能或尚未初始化
+    NotEnabled,
+    /// 接收 FIFO 溢出，有数据丢失
+    Overrun,
+    /// 帧错误（没有收到有效的停止位）
+    Framing,
+    /// 奇偶校验错误
+    Parity,
+    /// 收到 break
+    Break,
+    /// 硬件流控下对端撤销 CTS 超时
+    FlowControlTimeout,
+    /// 忙等超过自旋上限，或复位后发送器仍然忙
+    TimedOut,
+    /// 当前时钟无法产生请求的波特率
+    InvalidBaud,
+    /// 帧长度超过缓冲区，或超过长度字段能表示的范围
+    FrameTooLong,
+    /// 长度字段宽度不是 1、2 或 4 字节
+    InvalidPrefix,
+    /// 寄存器基地址为 0
+    NullBase,
+    /// 寄存器基地址没有按 4 字节对齐
+    Misaligned,
+    /// 映射长度小于寄存器块
+    RegionTooSmall,
+}
+
+impl From<ReadError> for Error {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Overrun => Self::Overrun,
+            ReadError::Break => Self::Break,
+            ReadError::Parity(_) => Self::Parity,
+            ReadError::Framing => Self::Framing,
+        }
+    }
+}
+
+impl From<WriteError> for Error {
+    fn from(err: WriteError) -> Self {
+        match err {
+            WriteError::FlowControlTimeout { .. } => Self::FlowControlTimeout,
+        }
+    }
+}
+
+impl From<TimedOut> for Error {
+    fn from(_: TimedOut) -> Self {
+        Self::TimedOut
+    }
+}
+
+impl From<RecoverError> for Error {
+    fn from(err: RecoverError) -> Self {
+        match err {
+            RecoverError::StillBusy => Self::TimedOut,
+        }
+    }
+}
+
+impl From<InitError> for Error {
+    fn from(_: InitError) -> Self {
+        Self::InvalidBaud
+    }
+}
+
+impl From<InvalidBaud> for Error {
+    fn from(_: InvalidBaud) -> Self {
+        Self::InvalidBaud
+    }
+}
+
+impl From<MapError> for Error {
+    fn from(err: MapError) -> Self {
+        match err {
+            MapError::Null => Self::NullBase,
+            MapError::Misaligned => Self::Misaligned,
+            MapError::TooSmall => Self::RegionTooSmall,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! 长度前缀帧：先收发固定宽度的长度字段，再收发对应长度的负载。
+
+use crate::{error::Error, pl011::Uart, wake::WakeSource};
+
+/// 长度字段的字节序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// 小端
+    Le,
+    /// 大端
+    Be,
+}
+
+/// 长度字段的格式，`read_framed` 和 `write_framed` 共用，保证收发对称
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixFormat {
+    /// 长度字段占用的字节数，只能是 1、2 或 4
+    pub width: u8,
+    /// 多字节长度字段的字节序，1 字节时不起作用
+    pub endian: Endian,
+}
+
+impl PrefixFormat {
+    /// 1 字节
+    pub const U8: Self = Self::new(1, Endian::Le);
+    /// 2 字节，小端
+    pub const U16_LE: Self = Self::new(2, Endian::Le);
+    /// 2 字节，大端
+    pub const U16_BE: Self = Self::new(2, Endian::Be);
+    /// 4 字节，小端
+    pub const U32_LE: Self = Self::new(4, Endian::Le);
+    /// 4 字节，大端
+    pub const U32_BE: Self = Self::new(4, Endian::Be);
+
+    pub const fn new(width: u8, endian: Endian) -> Self {
+        Self { width, endian }
+    }
+
+    // 宽度是否为 1、2 或 4 字节
+    fn is_valid(self) -> bool {
+        matches!(self.width, 1 | 2 | 4)
+    }
+
+    /// 长度字段能表示的最大负载长度
+    pub const fn max_len(self) -> usize {
+        match self.width {
+            1 => u8::MAX as usize,
+            2 => u16::MAX as usize,
+            _ => u32::MAX as usize,
+        }
+    }
+
+    // 从前 `width` 字节的长度字段解析负载长度
+    fn decode(self, bytes: &[u8; 4]) -> u32 {
+        let width = self.width as usize;
+        let mut value = [0; 4];
+        match self.endian {
+            Endian::Le => value[..width].copy_from_slice(&bytes[..width]),
+            Endian::Be => value[4 - width..].copy_from_slice(&bytes[..width]),
+        }
+        match self.endian {
+            Endian::Le => u32::from_le_bytes(value),
+            Endian::Be => u32::from_be_bytes(value),
+        }
+    }
+
+    // 把负载长度编码到前 `width` 字节，调用者保证不超过 `max_len`
+    fn encode(self, len: u32) -> [u8; 4] {
+        let width = self.width as usize;
+        let mut bytes = [0; 4];
+        match self.endian {
+            Endian::Le => bytes[..width].copy_from_slice(&len.to_le_bytes()[..width]),
+            Endian::Be => bytes[..width].copy_from_slice(&len.to_be_bytes()[4 - width..]),
+        }
+        bytes
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> Uart<DEPTH, W> {
+    /// 设置 `read_framed` 和 `write_framed` 使用的长度字段格式，默认为 1 字节。
+    ///
+    /// 宽度不是 1、2 或 4 字节时返回 `Error::InvalidPrefix`，保持原来的设置。
+    pub fn set_frame_prefix(&mut self, prefix: PrefixFormat) -> Result<(), Error> {
+        if !prefix.is_valid() {
+            return Err(Error::InvalidPrefix);
+        }
+        self.frame_prefix = prefix;
+        Ok(())
+    }
+
+    /// 接收一个长度前缀帧：先读取长度字段，再读取恰好该长度的负载到 `buf`，返回负载长度。
+    ///
+    /// 长度超过 `buf` 时返回 `Error::FrameTooLong`，负载留在接收 FIFO 中不读取，
+    /// 长度字段可能已经损坏，由调用者按协议重新同步。
+    pub async fn read_framed(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let prefix = self.frame_prefix;
+        let mut bytes = [0; 4];
+        self.read(&mut bytes[..prefix.width as usize]).await?;
+        let len = prefix.decode(&bytes) as usize;
+        let payload = buf.get_mut(..len).ok_or(Error::FrameTooLong)?;
+        self.read(payload).await?;
+        Ok(len)
+    }
+
+    /// 发送一个长度前缀帧：先按 `set_frame_prefix` 的格式发送 `payload` 的长度，再发送负载，
+    /// 返回负载长度。
+    ///
+    /// 长度超过长度字段能表示的范围时返回 `Error::FrameTooLong`，不发送任何数据。
+    pub async fn write_framed(&mut self, payload: &[u8]) -> Result<usize, Error> {
+        let prefix = self.frame_prefix;
+        if payload.len() > prefix.max_len() {
+            return Err(Error::FrameTooLong);
+        }
+        let bytes = prefix.encode(payload.len() as u32);
+        self.write(&bytes[..prefix.width as usize]).await;
+        Ok(self.write(payload).await)
+    }
+}
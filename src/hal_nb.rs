@@ -0,0 +1,59 @@
+//! `embedded-hal-nb` 串口 trait 实现，需要启用 `embedded-hal` feature。
+//!
+//! 原生的异步接口仍然是主要接口，这里只为基于 `nb` 的协议驱动提供兼容层。
+
+use embedded_hal_nb::{
+    nb,
+    serial::{Error, ErrorKind, ErrorType, Read, Write},
+};
+use tock_registers::interfaces::{Readable, Writeable};
+
+use crate::{
+    pl011::{ReadError, Uart},
+    regs::{DATA, FLAG},
+    wake::WakeSource,
+};
+
+impl Error for ReadError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ReadError::Overrun => ErrorKind::Overrun,
+            ReadError::Parity(_) => ErrorKind::Parity,
+            ReadError::Framing => ErrorKind::FrameFormat,
+            ReadError::Break => ErrorKind::Other,
+        }
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> ErrorType for Uart<DEPTH, W> {
+    type Error = ReadError;
+}
+
+impl<const DEPTH: usize, W: WakeSource> Read for Uart<DEPTH, W> {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        // 丢弃字节和错误标志的处理都在 `receive_checked` 中
+        self.receive_checked()
+            .ok_or(nb::Error::WouldBlock)?
+            .map_err(nb::Error::Other)
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> Write for Uart<DEPTH, W> {
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        let uart = unsafe { self.base.as_ref() };
+        if uart.uartfr.is_set(FLAG::TXFF) {
+            return Err(nb::Error::WouldBlock);
+        }
+        uart.uartdr.write(DATA::RAW.val(word as u32));
+        self.note_tx(1);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        let uart = unsafe { self.base.as_ref() };
+        if uart.uartfr.is_set(FLAG::BUSY) {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,51 @@
 #![no_std]
-#![feature(const_option)]
-#![feature(const_nonnull_new)]
 
+extern crate alloc;
+
+#[cfg(feature = "embedded-io")]
+pub mod buf_reader;
+pub mod buffered;
+pub mod error;
 pub mod pl011;
+pub mod registry;
+pub mod regs;
+pub mod wake;
+
+#[cfg(feature = "cobs")]
+pub mod cobs;
+#[cfg(feature = "async")]
+pub mod framed;
+#[cfg(feature = "embedded-hal")]
+mod hal_nb;
+#[cfg(feature = "async")]
+pub mod line;
+#[cfg(feature = "log-backend")]
+pub mod logger;
+#[cfg(feature = "test-mock")]
+mod mock;
+#[cfg(feature = "critical-section")]
+pub mod shared;
+#[cfg(feature = "slip")]
+pub mod slip;
 
-pub use pl011::Uart;
+#[cfg(feature = "embedded-io")]
+pub use buf_reader::UartBufReader;
+pub use buffered::BufferedUart;
+pub use error::Error;
+#[cfg(feature = "log-backend")]
+pub use logger::UartLogger;
+#[cfg(feature = "async")]
+pub use pl011::Transfer;
+pub use pl011::{
+    Baud, BaudDivisor, ClockHz, ClockProvider, ConfigGuard, ConfigWarning, ControlConfig, DataBits,
+    Direction, ErrorFlags, FifoLevel, FlowControl, Health, InitError, InterruptStatus, InvalidBaud,
+    LineConfig, LineHealth, MapError, OverrunRecovery, Parity, ParityErrorPolicy, PeripheralId,
+    QuickCheck, ReadError, RecoverError, ReuniteError, SavedState, StopBits, TimedOut, Uart,
+    UartConfig, UartFmt, UartRx, UartTx, Unsupported, WriteError, baud_divisor, compute_divisor,
+    decode_mis,
+};
+pub use registry::UartRegistry;
+pub use regs::{INTERRUPT, UartRegs};
+#[cfg(feature = "critical-section")]
+pub use shared::SharedUart;
+pub use wake::{DefaultWakeSource, NoWake, WakeSource};
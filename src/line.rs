@@ -0,0 +1,179 @@
+//! 文本行读取，以及 NMEA 等带 `*XX` 校验和的行协议支持。
+
+use crate::{
+    pl011::{ReadError, Uart},
+    wake::WakeSource,
+};
+
+/// 行读取错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineError {
+    /// 底层接收错误
+    Read(ReadError),
+    /// 行超过缓冲区长度，该行已被读完丢弃
+    TooLong,
+    /// 行末没有 `*XX` 形式的校验和
+    MissingChecksum,
+    /// 校验和与内容不符
+    ChecksumMismatch,
+}
+
+impl From<ReadError> for LineError {
+    fn from(err: ReadError) -> Self {
+        Self::Read(err)
+    }
+}
+
+/// `read_str` 的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Utf8Error {
+    /// 底层接收错误
+    Read(ReadError),
+    /// 收到无效的 UTF-8 序列，已读到的数据被丢弃
+    Invalid(core::str::Utf8Error),
+    /// `buf` 放不下暂存的不完整字符和它的下一个字节，暂存的数据保留，换用更大的缓冲区重试
+    BufferTooSmall,
+}
+
+impl From<ReadError> for Utf8Error {
+    fn from(err: ReadError) -> Self {
+        Self::Read(err)
+    }
+}
+
+/// 行校验和算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// 逐字节异或，NMEA 0183 使用
+    Xor,
+    /// 逐字节相加，取低 8 位
+    Additive,
+}
+
+impl Checksum {
+    /// 计算 `data` 的校验和
+    pub fn compute(self, data: &[u8]) -> u8 {
+        match self {
+            Checksum::Xor => data.iter().fold(0, |acc, &b| acc ^ b),
+            Checksum::Additive => data.iter().fold(0, |acc: u8, &b| acc.wrapping_add(b)),
+        }
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> Uart<DEPTH, W> {
+    /// 接收一行直到 `\n`，写入 `buf` 并返回长度，结果不含行尾的 `\n` 和 `\r`。
+    ///
+    /// 行超过 `buf` 时继续读到行尾以保持对齐，然后返回 `LineError::TooLong`。
+    /// 打开 `set_filter_nul` 后丢弃收到的 NUL 字节。
+    pub async fn read_line(&mut self, buf: &mut [u8]) -> Result<usize, LineError> {
+        let mut len = 0;
+        let mut overflow = false;
+        loop {
+            let mut byte = [0];
+            self.read(&mut byte).await?;
+            if byte[0] == b'\n' {
+                break;
+            }
+            if byte[0] == 0 && self.filter_nul {
+                continue;
+            }
+            match buf.get_mut(len) {
+                Some(slot) => {
+                    *slot = byte[0];
+                    len += 1;
+                }
+                None => overflow = true,
+            }
+        }
+        if overflow {
+            return Err(LineError::TooLong);
+        }
+        if len > 0 && buf[len - 1] == b'\r' {
+            len -= 1;
+        }
+        Ok(len)
+    }
+
+    /// 接收一行并校验行末 `*XX` 形式（两位十六进制）的校验和。
+    ///
+    /// 校验范围是行首的 `$` 或 `!` 之后到 `*` 之前的内容。成功时返回去掉
+    /// `*XX` 之后的行长度，`buf` 中保留原始内容。
+    pub async fn read_checked_line(
+        &mut self,
+        buf: &mut [u8],
+        checksum: Checksum,
+    ) -> Result<usize, LineError> {
+        let len = self.read_line(buf).await?;
+        let line = &buf[..len];
+        let star = match line.iter().rposition(|&b| b == b'*') {
+            Some(star) if star + 3 == len => star,
+            _ => return Err(LineError::MissingChecksum),
+        };
+        let expected = core::str::from_utf8(&line[star + 1..])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(LineError::MissingChecksum)?;
+        let body = match line.first() {
+            Some(b'$' | b'!') => &line[1..star],
+            _ => &line[..star],
+        };
+        if checksum.compute(body) != expected {
+            return Err(LineError::ChecksumMismatch);
+        }
+        Ok(star)
+    }
+
+    /// 接收数据填满 `buf`，校验 UTF-8 后以 `&str` 返回。
+    ///
+    /// 末尾不完整的多字节字符不会返回，而是暂存起来，在下次调用时放到 `buf`
+    /// 开头，因此字符不会被拆到两次读取中。收到无效序列时返回
+    /// `Utf8Error::Invalid`。有暂存字节而 `buf` 不比它们长时无法取得进展，
+    /// 返回 `Utf8Error::BufferTooSmall`，不读取数据；多字节字符最长 4 字节，
+    /// `buf` 不短于 4 字节时不会出现。
+    pub async fn read_str<'a>(&'a mut self, buf: &'a mut [u8]) -> Result<&'a str, Utf8Error> {
+        let pending = self.utf8_pending_len;
+        if pending > 0 && pending >= buf.len() {
+            return Err(Utf8Error::BufferTooSmall);
+        }
+        buf[..pending].copy_from_slice(&self.utf8_pending[..pending]);
+        self.read(&mut buf[pending..]).await?;
+        self.utf8_pending_len = 0;
+        let valid = match core::str::from_utf8(buf) {
+            Ok(_) => buf.len(),
+            Err(err) if err.error_len().is_none() => {
+                // 不完整的尾部放回暂存区，下次调用时接在 `buf` 开头
+                let valid = err.valid_up_to();
+                let tail = &buf[valid..];
+                self.utf8_pending[..tail.len()].copy_from_slice(tail);
+                self.utf8_pending_len = tail.len();
+                valid
+            }
+            Err(err) => return Err(Utf8Error::Invalid(err)),
+        };
+        Ok(core::str::from_utf8(&buf[..valid]).unwrap())
+    }
+
+    /// 接收数据追加到 `out`，直到收到 `delim`，`delim` 本身不写入 `out`。
+    ///
+    /// `out` 写满时仍未收到 `delim` 则返回 `LineError::TooLong`，已收到的数据保留在
+    /// `out` 中，其余数据留在 FIFO 里由后续读取处理。
+    #[cfg(feature = "heapless")]
+    pub async fn read_until<const N: usize>(
+        &mut self,
+        delim: u8,
+        out: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), LineError> {
+        loop {
+            if out.is_full() {
+                return Err(LineError::TooLong);
+            }
+            let mut byte = [0];
+            self.read(&mut byte).await?;
+            if byte[0] == delim {
+                return Ok(());
+            }
+            // 上面已经检查过容量
+            let _ = out.push(byte[0]);
+        }
+    }
+}
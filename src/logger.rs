@@ -0,0 +1,68 @@
+//! 把 `Uart` 注册为 `log` 后端，需要启用 `log-backend` feature。
+
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    pl011::{Uart, UartFmt},
+    wake::{DefaultWakeSource, WakeSource},
+};
+
+/// 通过阻塞发送输出日志的 `log::Log` 实现。
+///
+/// ```ignore
+/// static UART_LOGGER: UartLogger = UartLogger::new(Uart::new(UART_BASE as *mut u8));
+///
+/// log::set_logger(&UART_LOGGER).unwrap();
+/// log::set_max_level(log::LevelFilter::Info);
+/// ```
+///
+/// 输出一条日志的过程中再次记录日志（例如在中断处理中，或格式化参数时）会直接丢弃
+/// 后一条，不会死锁。
+pub struct UartLogger<const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: Uart<DEPTH, W>,
+    busy: AtomicBool,
+}
+
+impl<const DEPTH: usize, W: WakeSource> UartLogger<DEPTH, W> {
+    pub const fn new(uart: Uart<DEPTH, W>) -> Self {
+        Self {
+            uart,
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    /// 底层 `Uart`，用于初始化和中断处理
+    pub fn uart(&self) -> &Uart<DEPTH, W> {
+        &self.uart
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> log::Log for UartLogger<DEPTH, W> {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if self.busy.swap(true, Ordering::Acquire) {
+            return;
+        }
+        let _ = write!(
+            UartFmt(&self.uart),
+            "[{:<5} {}] {}\r\n",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        self.busy.store(false, Ordering::Release);
+    }
+
+    fn flush(&self) {
+        let _ = self.uart.flush();
+    }
+}
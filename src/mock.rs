@@ -0,0 +1,63 @@
+//! 在内存模拟的寄存器块上模拟中断，用于不依赖硬件测试异步路径，需要启用 `test-mock` feature。
+//!
+//! 这些方法直接改写寄存器块中的只读状态寄存器，只能用于指向普通内存的 `Uart`，
+//! 不能用于真实硬件。
+
+use core::mem::offset_of;
+
+use crate::{
+    pl011::Uart,
+    regs::{FLAG, INTERRUPT, UartRegs},
+    wake::WakeSource,
+};
+
+// 寄存器偏移和位值都取自 `regs` 中的寄存器表，修改寄存器表时这里随之更新
+const UARTDR: usize = offset_of!(UartRegs, uartdr);
+const UARTFR: usize = offset_of!(UartRegs, uartfr);
+const UARTRIS: usize = offset_of!(UartRegs, uartris);
+const UARTMIS: usize = offset_of!(UartRegs, uartmis);
+
+const FR_BUSY: u32 = FLAG::BUSY.mask << FLAG::BUSY.shift;
+const FR_RXFE: u32 = FLAG::RXFE.mask << FLAG::RXFE.shift;
+const FR_TXFF: u32 = FLAG::TXFF.mask << FLAG::TXFF.shift;
+const FR_TXFE: u32 = FLAG::TXFE.mask << FLAG::TXFE.shift;
+const INT_RX: u32 = INTERRUPT::RXIM.mask << INTERRUPT::RXIM.shift;
+const INT_TX: u32 = INTERRUPT::TXIM.mask << INTERRUPT::TXIM.shift;
+
+impl<const DEPTH: usize, W: WakeSource> Uart<DEPTH, W> {
+    fn mock_reg(&self, offset: usize) -> *mut u32 {
+        unsafe { (self.base.as_ptr() as *mut u32).add(offset / 4) }
+    }
+
+    fn mock_update(&self, offset: usize, set: u32, clear: u32) {
+        let reg = self.mock_reg(offset);
+        unsafe { reg.write_volatile((reg.read_volatile() | set) & !clear) };
+    }
+
+    /// 模拟收到一个字节：放进数据寄存器，清除 `RXFE`，置位接收中断状态并调用
+    /// `handle_interrupt`。
+    ///
+    /// 模拟的寄存器没有 FIFO，读取数据寄存器不会置位 `RXFE`，字节会一直留在数据寄存器中，
+    /// 每次读取都得到同一个字节。逐字节驱动读 future 时用单字节的缓冲区，在两次调用之间
+    /// 轮询 future。
+    pub fn simulate_rx(&self, byte: u8) {
+        unsafe { self.mock_reg(UARTDR).write_volatile(byte as u32) };
+        self.mock_update(UARTFR, 0, FR_RXFE);
+        self.mock_update(UARTRIS, INT_RX, 0);
+        self.mock_update(UARTMIS, INT_RX, 0);
+        self.handle_interrupt();
+        // handle_interrupt 写 ICR 不会清除模拟的状态位，这里手动清除
+        self.mock_update(UARTRIS, 0, INT_RX);
+        self.mock_update(UARTMIS, 0, INT_RX);
+    }
+
+    /// 模拟发送完成：发送 FIFO 为空且线路空闲，置位发送中断状态并调用 `handle_interrupt`。
+    pub fn simulate_tx_complete(&self) {
+        self.mock_update(UARTFR, FR_TXFE, FR_TXFF | FR_BUSY);
+        self.mock_update(UARTRIS, INT_TX, 0);
+        self.mock_update(UARTMIS, INT_TX, 0);
+        self.handle_interrupt();
+        self.mock_update(UARTRIS, 0, INT_TX);
+        self.mock_update(UARTMIS, 0, INT_TX);
+    }
+}
@@ -1,183 +1,3725 @@
+#[cfg(feature = "async")]
+use aarch64_cpu::registers::{CNTFRQ_EL0, CNTPCT_EL0, Readable as _};
+use alloc::sync::Arc;
 use core::{
-    pin::Pin,
+    ops::Deref,
     ptr::NonNull,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicUsize, Ordering, compiler_fence},
+};
+#[cfg(feature = "async")]
+use core::{
+    pin::Pin,
+    sync::atomic::AtomicU64,
     task::{Context, Poll},
 };
-use futures::task::AtomicWaker;
-use log::{info, warn};
+use kspin::SpinNoIrq;
+use log::{debug, info, warn};
 use tock_registers::{
-    interfaces::{Readable, Writeable},
-    register_bitfields, register_structs,
-    registers::{ReadOnly, ReadWrite, WriteOnly},
+    LocalRegisterCopy,
+    fields::FieldValue,
+    interfaces::{ReadWriteable, Readable, Writeable},
 };
 
-register_structs! {
-    UartRegs {
-        (0x000 => uartdr: ReadWrite<u32, DATA::Register>),
-        (0x004 => uartecr: ReadOnly<u32>),
-        (0x008 => _reserved0),
-        (0x018 => uartfr: ReadOnly<u32, FLAG::Register>),
-        (0x01c => _reserved1),
-        (0x024 => uartibrd: ReadWrite<u32>),
-        (0x028 => uartfbrd: ReadWrite<u32>),
-        (0x02c => uartlcrh: ReadWrite<u32>),
-        (0x030 => uartcr: ReadWrite<u32>),
-        (0x034 => uartifls: ReadWrite<u32, FIFO::Register>),
-        (0x038 => uartimsc: ReadWrite<u32, INTERRUPT::Register>),
-        (0x03c => uartris: ReadOnly<u32>),
-        (0x040 => uartmis: ReadOnly<u32>),
-        (0x044 => uarticr: WriteOnly<u32, ICR::Register>),
-        (0x048 => uartdmacr: ReadWrite<u32>),
-        (0x04c => @END),
-    }
-}
-
-register_bitfields![u32,
-    DATA [
-        RAW OFFSET(0) NUMBITS(8),
-        FE OFFSET(9) NUMBITS(1),
-        PE OFFSET(10) NUMBITS(1),
-        BE OFFSET(11) NUMBITS(1),
-        OE OFFSET(12) NUMBITS(1),
-    ],
-    FLAG [
-        CTS OFFSET(0) NUMBITS(1),
-        DSR OFFSET(1) NUMBITS(1),
-        DCD OFFSET(2) NUMBITS(1),
-        BUSY OFFSET(3) NUMBITS(1),
-        RXFE OFFSET(4) NUMBITS(1),
-        TXFF OFFSET(5) NUMBITS(1),
-        RXFF OFFSET(6) NUMBITS(1),
-        TXFE OFFSET(7) NUMBITS(1),
-    ],
-    FIFO [
-        TXSEL OFFSET(0) NUMBITS(3) [
-            TX1_8 = 0,
-            TX1_4 = 1,
-            TX1_2 = 2,
-            TX3_4 = 3,
-            TX7_8 = 4,
-        ],
-        RXSEL OFFSET(3) NUMBITS(3) [
-            RX1_8 = 0,
-            RX1_4 = 1,
-            RX1_2 = 2,
-            RX3_4 = 3,
-            RX7_8 = 4,
-        ],
-    ],
-    INTERRUPT [
-        RXIM OFFSET(4) NUMBITS(1),
-        TXIM OFFSET(5) NUMBITS(1),
-    ],
-    ICR [
-        RXIC OFFSET(4) NUMBITS(1),
-        TXIC OFFSET(5) NUMBITS(1),
-        RTIC OFFSET(6) NUMBITS(1),
-        FEIC OFFSET(7) NUMBITS(1),
-        PEIC OFFSET(8) NUMBITS(1),
-        BEIC OFFSET(9) NUMBITS(1),
-        OEIC OFFSET(10) NUMBITS(1),
-    ]
-];
-
-pub struct Uart {
-    pub base: NonNull<UartRegs>,
-    waker: AtomicWaker,
-    pub irq_conut: usize,
-}
+use crate::error::Error;
+use crate::regs::*;
+use crate::wake::{DefaultWakeSource, WakeSource};
+
+/// 波特率（bit/s）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Baud(pub u32);
+
+/// UART 参考时钟频率（Hz）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockHz(pub u32);
+
+impl From<u32> for Baud {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<u32> for ClockHz {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+/// 提供 UART 参考时钟的当前频率，用于时钟会动态调整的 SoC。
+pub trait ClockProvider {
+    fn uart_clk_hz(&self) -> ClockHz;
+}
+
+// 固定频率的时钟
+impl ClockProvider for ClockHz {
+    fn uart_clk_hz(&self) -> ClockHz {
+        *self
+    }
+}
+
+/// 数据位宽
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+/// 停止位
+///
+/// PL011 只有 1 位和 2 位两种设置。`OneAndHalf` 与 `Two` 写入相同的 STP2 位，
+/// 在 5 位数据宽度下硬件实际产生 1.5 个停止位，其他宽度下为 2 个停止位。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+    OneAndHalf,
+}
+
+/// 奇偶校验
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+/// 帧格式配置，默认为 8N1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl Default for LineConfig {
+    fn default() -> Self {
+        Self {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+}
+
+impl DataBits {
+    fn wlen(self) -> FieldValue<u32, LCRH::Register> {
+        match self {
+            DataBits::Five => LCRH::WLEN::Five,
+            DataBits::Six => LCRH::WLEN::Six,
+            DataBits::Seven => LCRH::WLEN::Seven,
+            DataBits::Eight => LCRH::WLEN::Eight,
+        }
+    }
+}
+
+impl LineConfig {
+    fn lcrh(&self) -> FieldValue<u32, LCRH::Register> {
+        let wlen = self.data_bits.wlen();
+        let parity = match self.parity {
+            Parity::None => LCRH::PEN::CLEAR,
+            Parity::Odd => LCRH::PEN::SET + LCRH::EPS::CLEAR,
+            Parity::Even => LCRH::PEN::SET + LCRH::EPS::SET,
+        };
+        let stop = match self.stop_bits {
+            StopBits::One => LCRH::STP2::CLEAR,
+            StopBits::Two => LCRH::STP2::SET,
+            StopBits::OneAndHalf => {
+                if self.data_bits != DataBits::Five {
+                    warn!("1.5 stop bits only exist with 5 data bits, using 2 stop bits");
+                }
+                LCRH::STP2::SET
+            }
+        };
+        wlen + parity + stop
+    }
+}
+
+/// FIFO 中断触发水位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoLevel {
+    OneEighth = 0,
+    Quarter = 1,
+    Half = 2,
+    ThreeQuarters = 3,
+    SevenEighths = 4,
+}
+
+impl FifoLevel {
+    /// 深度为 `fifo_depth` 的 FIFO 在该水位下对应的字节数，例如 16 字节 FIFO 的 1/8 为 2 字节。
+    pub const fn threshold_bytes(self, fifo_depth: usize) -> usize {
+        let eighths = match self {
+            FifoLevel::OneEighth => 1,
+            FifoLevel::Quarter => 2,
+            FifoLevel::Half => 4,
+            FifoLevel::ThreeQuarters => 6,
+            FifoLevel::SevenEighths => 7,
+        };
+        fifo_depth * eighths / 8
+    }
+}
+
+/// 使用的传输方向，用于只连接了 TX 或 RX 的场合
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    TxOnly,
+    RxOnly,
+    Both,
+}
+
+/// 硬件流控
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+    None,
+    /// 只使能 RTS
+    Rts,
+    /// 只使能 CTS
+    Cts,
+    RtsCts,
+}
+
+impl FlowControl {
+    // 对应 `uartcr` 的 RTSEN/CTSEN
+    fn cr_fields(self) -> FieldValue<u32, CR::Register> {
+        let (rtsen, ctsen) = match self {
+            FlowControl::None => (0, 0),
+            FlowControl::Rts => (1, 0),
+            FlowControl::Cts => (0, 1),
+            FlowControl::RtsCts => (1, 1),
+        };
+        CR::RTSEN.val(rtsen) + CR::CTSEN.val(ctsen)
+    }
+
+    fn from_cr(cr: LocalRegisterCopy<u32, CR::Register>) -> Self {
+        match (cr.is_set(CR::RTSEN), cr.is_set(CR::CTSEN)) {
+            (false, false) => FlowControl::None,
+            (true, false) => FlowControl::Rts,
+            (false, true) => FlowControl::Cts,
+            (true, true) => FlowControl::RtsCts,
+        }
+    }
+}
+
+/// `uartcr` 中常用控制位的结构化表示，见 `Uart::set_control`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlConfig {
+    pub uart_enable: bool,
+    pub tx_enable: bool,
+    pub rx_enable: bool,
+    /// 软件控制的 RTS 输出，硬件 RTS 流控使能时由硬件控制
+    pub rts: bool,
+    pub loopback: bool,
+    pub flow_control: FlowControl,
+}
+
+/// 从寄存器读回的 UART 配置，见 `Uart::current_config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    pub line: LineConfig,
+    pub fifo_enabled: bool,
+    pub uart_enabled: bool,
+    pub tx_enabled: bool,
+    pub rx_enabled: bool,
+    pub flow_control: FlowControl,
+    /// 分频值 `(整数部分, 小数部分)`
+    pub divisor: (u32, u32),
+}
+
+impl UartConfig {
+    /// 按参考时钟由分频值反算波特率，可以和 `line` 一起传给 `Uart::init_with`。
+    pub fn baud(&self, clk: ClockHz) -> Baud {
+        let (integer_part, fraction_part) = self.divisor;
+        let divisor = 64 * integer_part as u64 + fraction_part as u64;
+        if divisor == 0 {
+            return Baud(0);
+        }
+        Baud((4 * clk.0 as u64 / divisor) as u32)
+    }
+}
+
+/// 计算波特率分频值，返回 `(整数部分, 小数部分)`。
+///
+/// 小数部分按 1/64 四舍五入。`uartfbrd` 只有 6 位，舍入到 64 时进位到整数部分。
+pub const fn baud_divisor(clk: ClockHz, baud: Baud) -> (u32, u32) {
+    let clk_rate = clk.0 as u64;
+    let baud_rate = baud.0 as u64;
+    let mut integer_part = clk_rate / (16 * baud_rate);
+    let remainder = clk_rate % (16 * baud_rate);
+    let mut fraction_part = (remainder * 64 + 8 * baud_rate) / (16 * baud_rate);
+    if fraction_part == 64 {
+        integer_part += 1;
+        fraction_part = 0;
+    }
+    (integer_part as u32, fraction_part as u32)
+}
+
+// 检查分频值能否写入 `uartibrd`/`uartfbrd`，init、reconfigure 和 set_divisor 共用。
+// TRM 规定整数部分为 0xFFFF 时小数部分必须为 0
+fn check_divisor(integer_part: u32, fraction_part: u32) -> Result<(), InitError> {
+    if integer_part == 0 {
+        Err(InitError::BaudTooHigh)
+    } else if integer_part > 0xFFFF {
+        Err(InitError::BaudTooLow)
+    } else if fraction_part > 63 {
+        Err(InitError::InvalidFraction)
+    } else if integer_part == 0xFFFF && fraction_part != 0 {
+        Err(InitError::BaudTooLow)
+    } else {
+        Ok(())
+    }
+}
+
+// init 的各个步骤，按 PL011 TRM 要求的先后顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum InitStep {
+    Disable,
+    WaitIdle,
+    FlushFifo,
+    Divisor,
+    LineControl,
+    Enable,
+}
+
+// 记录 init 已经执行到的步骤，开启 `init-check` feature 时检查顺序和硬件状态，
+// 发现违反 TRM 顺序的写入时输出警告，只用于开发调试
+struct InitSeq {
+    #[cfg(feature = "init-check")]
+    last: Option<InitStep>,
+}
+
+impl InitSeq {
+    fn new() -> Self {
+        Self {
+            #[cfg(feature = "init-check")]
+            last: None,
+        }
+    }
+
+    // 在执行 `step` 对应的寄存器写入之前调用
+    #[cfg_attr(not(feature = "init-check"), allow(unused_variables))]
+    fn step(&mut self, step: InitStep, uart: &UartRegs) {
+        #[cfg(feature = "init-check")]
+        {
+            if let Some(last) = self.last.filter(|&last| last >= step) {
+                warn!("init step {step:?} written after {last:?}");
+            }
+            self.last = Some(step);
+            let enabled = uart.uartcr.is_set(CR::UARTEN);
+            let busy = uart.uartfr.is_set(FLAG::BUSY);
+            let fifo = uart.uartlcrh.is_set(LCRH::FEN);
+            match step {
+                InitStep::FlushFifo | InitStep::Divisor | InitStep::LineControl if enabled => {
+                    warn!("init step {step:?} written while UART enabled");
+                }
+                InitStep::FlushFifo | InitStep::Divisor if busy => {
+                    warn!("init step {step:?} written while UART busy");
+                }
+                InitStep::Divisor if fifo => {
+                    warn!("init step {step:?} written before FIFO flush");
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// 在临界区内执行修改中断屏蔽的操作，未启用 `critical-section` feature 时直接执行
+fn critical<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "critical-section")]
+    return critical_section::with(|_| f());
+    #[cfg(not(feature = "critical-section"))]
+    f()
+}
+
+/// 配置 UART 失败
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitError {
+    /// 分频值整数部分为 0，波特率超过时钟的 1/16
+    BaudTooHigh,
+    /// 分频值超过 `uartibrd` 的 16 位（整数部分为 0xFFFF 时小数部分只能为 0）
+    BaudTooLow,
+    /// 分频值小数部分超过 `uartfbrd` 的 6 位
+    InvalidFraction,
+}
+
+/// `compute_divisor` 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaudDivisor {
+    /// 写入 `uartibrd` 的整数部分
+    pub integer: u32,
+    /// 写入 `uartfbrd` 的小数部分（1/64）
+    pub fraction: u32,
+    /// 实际得到的波特率
+    pub actual: Baud,
+    /// 实际波特率相对请求值的误差（百万分之一）
+    pub error_ppm: i32,
+}
+
+/// 无法用当前时钟产生请求的波特率
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidBaud {
+    /// 波特率为 0
+    Zero,
+    /// 分频值小于 1，波特率超过时钟的 1/16
+    TooHigh,
+    /// 整数部分超过 `uartibrd` 的 16 位，或为 0xFFFF 且带小数部分
+    TooLow,
+}
+
+/// 计算分频值并检查是否可用，同时给出实际波特率和误差。
+///
+/// 高波特率下分频值很小，舍入误差会明显变大，建议检查 `error_ppm`。
+pub fn compute_divisor(clk: ClockHz, baud: Baud) -> Result<BaudDivisor, InvalidBaud> {
+    if baud.0 == 0 {
+        return Err(InvalidBaud::Zero);
+    }
+    let (integer, fraction) = baud_divisor(clk, baud);
+    if integer == 0 {
+        return Err(InvalidBaud::TooHigh);
+    }
+    // 与 `InitError::BaudTooLow` 一致，整数部分为 0xFFFF 时小数部分只能为 0
+    if integer > 0xFFFF || (integer == 0xFFFF && fraction != 0) {
+        return Err(InvalidBaud::TooLow);
+    }
+    let divisor = 64 * integer as u64 + fraction as u64;
+    let actual = 4 * clk.0 as u64 / divisor;
+    let error_ppm = (actual as i64 - baud.0 as i64) * 1_000_000 / baud.0 as i64;
+    Ok(BaudDivisor {
+        integer,
+        fraction,
+        actual: Baud(actual as u32),
+        error_ppm: error_ppm as i32,
+    })
+}
+
+/// PL011 UART 驱动
+///
+/// `DEPTH` 是硬件 FIFO 深度（PL011 为 16，部分集成为 32），决定批量写入时
+/// 一次填充 FIFO 的字节数。
+pub struct Uart<const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    pub base: NonNull<UartRegs>,
+    waker: W,
+    rx_waker: W,
+    // 中断中检测到、尚未报告给读操作的接收错误
+    rx_errors: AtomicU32,
+    // 中断中观察到接收溢出，由 rx_overran_since 清除
+    rx_overran: AtomicBool,
+    break_waker: W,
+    // 每接收多少字节撤销一次 RTS，0 表示不节流
+    #[cfg(feature = "async")]
+    rx_pacing: usize,
+    #[cfg(feature = "async")]
+    rx_paced: AtomicUsize,
+    break_count: AtomicUsize,
+    // break 的最短长度（位时间），0 表示不过滤
+    #[cfg(feature = "async")]
+    break_threshold: u32,
+    // 等待判断长度的 break 的检测时刻（CNTPCT），0 表示没有
+    #[cfg(feature = "async")]
+    break_started: AtomicU64,
+    idle_waker: W,
+    // 接收超时中断（线路空闲）的次数
+    idle_count: AtomicUsize,
+    cts_waker: W,
+    // CTS 调制解调器状态中断的次数
+    cts_count: AtomicUsize,
+    parity_policy: ParityErrorPolicy,
+    parity_discarded: AtomicUsize,
+    overrun_recovery: OverrunRecovery,
+    overrun_recovered: AtomicUsize,
+    overrun_discarded: AtomicUsize,
+    irq_count: AtomicUsize,
+    // 中断中检测到的接收溢出次数
+    overrun_count: AtomicUsize,
+    // 写 future 因发送 FIFO 满而返回 Pending 的次数
+    tx_backpressure: AtomicUsize,
+    // start_tx_dma 启动、尚未由 tx_dma_complete 确认完成的 DMA 发送
+    tx_dma_active: AtomicBool,
+    // 最近一次 init/set_baud 使用的参考时钟，用于换算位时间
+    clk: AtomicU32,
+    // 最近一次配置写入的 FEN；init 之前不确定硬件状态，按字符模式处理
+    fifo_enabled: AtomicBool,
+    spin_hook: fn(),
+    // RS-485 收发器方向切换，参数为 true 时使能发送（DE）
+    de_hook: Option<fn(bool)>,
+    // configure_rs485 之后 write_and_drain 按半双工时序切换 TXE/RXE
+    #[cfg(feature = "async")]
+    rs485: bool,
+    #[cfg(feature = "async")]
+    rs485_suppress_echo: bool,
+    busy_spin_cap: u32,
+    verbose: bool,
+    clear_rx_on_baud_change: bool,
+    // init/set_baud 之后丢弃的字节数，以及剩余待丢弃的字节数
+    warmup_bytes: u8,
+    warmup_left: AtomicU8,
+    // 半双工回显抑制：已发送但还没有从接收端丢弃的字节数
+    suppress_tx_echo: bool,
+    tx_echo_pending: AtomicUsize,
+    // 控制台读取时丢弃 NUL 字节
+    pub(crate) filter_nul: bool,
+    // 每次发送前导码
+    preamble: &'static [u8],
+    // read_framed 使用的长度前缀格式
+    #[cfg(feature = "async")]
+    pub(crate) frame_prefix: crate::framed::PrefixFormat,
+    // read_str 上次调用末尾尚不完整的 UTF-8 字节
+    #[cfg(feature = "async")]
+    pub(crate) utf8_pending: [u8; 3],
+    #[cfg(feature = "async")]
+    pub(crate) utf8_pending_len: usize,
+    cb_write: SpinNoIrq<Option<CallbackWrite>>,
+    cb_read: SpinNoIrq<Option<CallbackRead>>,
+    tx_ready: SpinNoIrq<Option<fn()>>,
+}
+
+// 由中断驱动、完成后回调的发送
+struct CallbackWrite {
+    data: &'static [u8],
+    index: usize,
+    on_done: fn(usize),
+    // 开始发送前 TXIM 是否已经使能，数据全部进入 FIFO 后恢复
+    txim_was_set: bool,
+    // TXIM 仍由本次发送占用（尚未恢复）
+    txim_armed: bool,
+}
+
+// 由中断直接填充、完成后回调的接收
+struct CallbackRead {
+    buf: &'static mut [u8],
+    index: usize,
+    on_done: fn(&'static mut [u8], usize),
+}
+
+/// PrimeCell 外设的 `UARTPCellID0..3` 固定值
+const PRIMECELL_ID: [u8; 4] = [0x0d, 0xf0, 0x05, 0xb1];
+
+/// 从 `UARTPeriphID0..3` 解码的外设标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeripheralId {
+    /// 设计者代码，ARM 为 `0x41`
+    pub designer: u8,
+    /// 部件号，PL011 为 `0x011`
+    pub part_number: u16,
+    /// 版本号，r1p5 为 3
+    pub revision: u8,
+    /// 配置位，PL011 为 0
+    pub configuration: u8,
+}
+
+impl PeripheralId {
+    /// 是否为 ARM 的 PL011
+    pub fn is_pl011(&self) -> bool {
+        self.designer == 0x41 && self.part_number == 0x011
+    }
+}
+
+/// `Uart::quick_check` 各项检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuickCheck {
+    /// `probe` 的结果：PrimeCell 标识正确，寄存器写入后能读回
+    pub probe: bool,
+    /// 读到的外设标识
+    pub id: PeripheralId,
+    /// 外设标识是否为 PL011
+    pub id_ok: bool,
+    /// 内部回环收发一个测试字节是否成功
+    pub loopback: bool,
+}
+
+impl QuickCheck {
+    /// 全部检查都通过
+    pub fn passed(&self) -> bool {
+        self.probe && self.id_ok && self.loopback
+    }
+}
+
+/// `Uart::from_addr` 的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// 地址为 0
+    Null,
+    /// 地址没有按 4 字节对齐
+    Misaligned,
+    /// 映射长度小于寄存器块
+    TooSmall,
+}
+
+/// 发送错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteError {
+    /// 硬件流控下对端撤销 CTS 超过了给定时间，携带已写入发送 FIFO 的字节数
+    FlowControlTimeout { written: usize },
+}
+
+/// `Uart::validate_config` 发现的容易导致收发停滞的配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigWarning {
+    /// 使能了接收中断但没有使能接收超时中断，FIFO 中少于触发水位的数据不会产生中断
+    RxTriggerWithoutTimeout,
+    /// 使能了接收中断但接收器（或 UART）关闭
+    RxInterruptWithReceiverDisabled,
+    /// 使能了发送中断但发送器（或 UART）关闭，发送 FIFO 不会排空
+    TxInterruptWithTransmitterDisabled,
+}
+
+/// 忙等超过 `Uart::set_spin_cap` 设置的自旋上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut;
+
+/// 硬件不支持请求的功能
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsupported;
+
+/// 接收错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadError {
+    /// 接收 FIFO 溢出，有数据丢失
+    Overrun,
+    /// 收到 break
+    Break,
+    /// 奇偶校验错误，携带出错的字节
+    Parity(u8),
+    /// 帧错误（没有收到有效的停止位）
+    Framing,
+}
+
+impl ReadError {
+    // 从 uartris/uartmis 的错误位解析错误，位置与 ICR 的清除位相同。
+    // 奇偶校验错误在读到出错字节时按 `ParityErrorPolicy` 处理，这里不报告。
+    #[cfg(feature = "async")]
+    fn from_status(status: u32) -> Option<Self> {
+        let is_set = |field: tock_registers::fields::Field<u32, ICR::Register>| {
+            status & (field.mask << field.shift) != 0
+        };
+        if is_set(ICR::OEIC) {
+            Some(Self::Overrun)
+        } else if is_set(ICR::BEIC) {
+            Some(Self::Break)
+        } else if is_set(ICR::FEIC) {
+            Some(Self::Framing)
+        } else {
+            None
+        }
+    }
+
+    // 从 uartdr 读到的数据中解析错误
+    pub(crate) fn from_data(data: LocalRegisterCopy<u32, DATA::Register>) -> Option<Self> {
+        if data.is_set(DATA::OE) {
+            Some(Self::Overrun)
+        } else if data.is_set(DATA::BE) {
+            Some(Self::Break)
+        } else if data.is_set(DATA::PE) {
+            Some(Self::Parity(data.read(DATA::RAW) as u8))
+        } else if data.is_set(DATA::FE) {
+            Some(Self::Framing)
+        } else {
+            None
+        }
+    }
+}
+
+/// `uartris` 中的接收错误标志，见 `Uart::peek_errors`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ErrorFlags {
+    pub overrun: bool,
+    pub break_detected: bool,
+    pub parity: bool,
+    pub framing: bool,
+}
+
+impl ErrorFlags {
+    // 从 uartris 的错误位解析，位置与 ICR 的清除位相同
+    fn from_status(status: u32) -> Self {
+        let is_set = |field: tock_registers::fields::Field<u32, ICR::Register>| {
+            status & (field.mask << field.shift) != 0
+        };
+        Self {
+            overrun: is_set(ICR::OEIC),
+            break_detected: is_set(ICR::BEIC),
+            parity: is_set(ICR::PEIC),
+            framing: is_set(ICR::FEIC),
+        }
+    }
+
+    /// 没有任何错误标志
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 收到奇偶校验错误字节时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParityErrorPolicy {
+    /// 丢弃出错字节并计数，读操作继续
+    Discard,
+    /// 读操作返回 `ReadError::Parity`，携带出错字节
+    Deliver,
+}
+
+/// `rx_line_health` 对接收线路状态的判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LineHealth {
+    /// 采样窗口内没有错误，线路空闲或正常收到数据
+    Ok,
+    /// 出错的帧多于正常的帧，常见于 RX 悬空拾取噪声
+    Floating,
+    /// 只收到 break，线路一直被拉低，常见于线缆断开或 TX/RX 接反
+    StuckBreak,
+}
+
+/// `Uart::health` 汇总的运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Health {
+    pub irq_count: usize,
+    pub overrun_count: usize,
+    pub break_count: usize,
+    pub tx_backpressure_events: usize,
+    /// 按当前的粘滞错误标志粗略判断，不采样
+    pub line: LineHealth,
+}
+
+/// 读操作遇到接收溢出时的恢复方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrunRecovery {
+    /// 保留 FIFO 中溢出前收到的数据，之后的读取继续读出
+    KeepBuffered,
+    /// 读出并丢弃 FIFO 中的全部数据，从下一个新收到的字节重新同步
+    FlushAll,
+}
+
+/// `uartmis` 中挂起的中断，见 `decode_mis`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InterruptStatus {
+    pub rx: bool,
+    pub tx: bool,
+    pub rx_timeout: bool,
+    pub framing: bool,
+    pub parity: bool,
+    pub break_: bool,
+    pub overrun: bool,
+    /// CTS 调制解调器状态变化
+    pub cts: bool,
+}
+
+/// 把 `uartmis`（或 `uartris`）的值解析为各个中断是否挂起。
+pub fn decode_mis(mis: u32) -> InterruptStatus {
+    let mis = LocalRegisterCopy::<u32, INTERRUPT::Register>::new(mis);
+    InterruptStatus {
+        rx: mis.is_set(INTERRUPT::RXIM),
+        tx: mis.is_set(INTERRUPT::TXIM),
+        rx_timeout: mis.is_set(INTERRUPT::RTIM),
+        framing: mis.is_set(INTERRUPT::FEIM),
+        parity: mis.is_set(INTERRUPT::PEIM),
+        break_: mis.is_set(INTERRUPT::BEIM),
+        overrun: mis.is_set(INTERRUPT::OEIM),
+        cts: mis.is_set(INTERRUPT::CTSMIM),
+    }
+}
+
+// uartris 中的接收错误位（FE/PE/BE/OE，第 7~10 位）
+const RX_ERROR_BITS: u32 = (1 << 7) | (1 << 8) | (1 << 9) | (1 << 10);
+// 其中随出错字节一起进入接收 FIFO 的错误位（FE/PE/BE），读取该字节时由 uartdr 再次报告
+#[cfg(feature = "async")]
+const RX_BYTE_ERROR_BITS: u32 = (1 << 7) | (1 << 8) | (1 << 9);
+// PL011 报告 BE 前线路至少已经保持低电平的位时间，按一帧估计
+#[cfg(feature = "async")]
+const BREAK_REPORT_BITS: u32 = 10;
+
+/// `Uart::recover` 的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoverError {
+    /// 重新使能 UART 后 BUSY 仍然置位
+    StillBusy,
+}
+
+// 寄存器访问都是 volatile 的，驱动状态使用原子变量或锁。在核之间迁移 `Uart` 时
+// 先调用 `transfer_ownership`，见其文档
+unsafe impl<const DEPTH: usize, W: WakeSource> Send for Uart<DEPTH, W> {}
+unsafe impl<const DEPTH: usize, W: WakeSource> Sync for Uart<DEPTH, W> {}
+
+impl<const DEPTH: usize, W: WakeSource> Uart<DEPTH, W> {
+    /// 硬件 FIFO 深度
+    pub const FIFO_DEPTH: usize = DEPTH;
+
+    pub const fn new(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap().cast(),
+            waker: W::INIT,
+            rx_waker: W::INIT,
+            rx_errors: AtomicU32::new(0),
+            rx_overran: AtomicBool::new(false),
+            break_waker: W::INIT,
+            #[cfg(feature = "async")]
+            rx_pacing: 0,
+            #[cfg(feature = "async")]
+            rx_paced: AtomicUsize::new(0),
+            break_count: AtomicUsize::new(0),
+            #[cfg(feature = "async")]
+            break_threshold: 0,
+            #[cfg(feature = "async")]
+            break_started: AtomicU64::new(0),
+            idle_waker: W::INIT,
+            idle_count: AtomicUsize::new(0),
+            cts_waker: W::INIT,
+            cts_count: AtomicUsize::new(0),
+            parity_policy: ParityErrorPolicy::Deliver,
+            parity_discarded: AtomicUsize::new(0),
+            overrun_recovery: OverrunRecovery::KeepBuffered,
+            overrun_recovered: AtomicUsize::new(0),
+            overrun_discarded: AtomicUsize::new(0),
+            irq_count: AtomicUsize::new(0),
+            overrun_count: AtomicUsize::new(0),
+            tx_backpressure: AtomicUsize::new(0),
+            tx_dma_active: AtomicBool::new(false),
+            clk: AtomicU32::new(0),
+            fifo_enabled: AtomicBool::new(false),
+            spin_hook: core::hint::spin_loop,
+            de_hook: None,
+            #[cfg(feature = "async")]
+            rs485: false,
+            #[cfg(feature = "async")]
+            rs485_suppress_echo: false,
+            busy_spin_cap: u32::MAX,
+            verbose: true,
+            clear_rx_on_baud_change: true,
+            warmup_bytes: 0,
+            warmup_left: AtomicU8::new(0),
+            suppress_tx_echo: false,
+            tx_echo_pending: AtomicUsize::new(0),
+            filter_nul: false,
+            preamble: &[],
+            #[cfg(feature = "async")]
+            frame_prefix: crate::framed::PrefixFormat::U8,
+            #[cfg(feature = "async")]
+            utf8_pending: [0; 3],
+            #[cfg(feature = "async")]
+            utf8_pending_len: 0,
+            cb_write: SpinNoIrq::new(None),
+            cb_read: SpinNoIrq::new(None),
+            tx_ready: SpinNoIrq::new(None),
+        }
+    }
+
+    /// 启动早期在 `iomap` 可用之前，直接用编译期已知的物理地址创建 `Uart`，
+    /// 可以放在 `static` 中作为早期控制台，在分配器和地址映射建立之前输出 panic 信息。
+    ///
+    /// 要求 `phys_base` 所在的页已经恒等映射（虚拟地址等于物理地址）为 Device 内存，
+    /// 例如 MMU 尚未开启，或引导阶段的页表已经映射了该地址；否则访问寄存器会触发异常，
+    /// 或按普通内存属性访问导致读写被缓存、合并。切换到正式页表后应改用映射后的地址
+    /// 重新创建 `Uart`。地址为 0 时在编译期（用于 `static` 时）或运行时 panic。
+    pub const fn new_early(phys_base: usize) -> Self {
+        Self::new(phys_base as *mut u8)
+    }
+
+    /// 从已映射的寄存器地址创建 `Uart`，检查地址非空、按 4 字节对齐，
+    /// 且映射长度 `len` 能容纳整个寄存器块。
+    ///
+    /// 寄存器都是 32 位的，AArch64 上未对齐的访问会触发异常。
+    pub fn from_addr(addr: usize, len: usize) -> Result<Self, MapError> {
+        if addr == 0 {
+            return Err(MapError::Null);
+        }
+        if !addr.is_multiple_of(4) {
+            return Err(MapError::Misaligned);
+        }
+        if len < core::mem::size_of::<UartRegs>() {
+            return Err(MapError::TooSmall);
+        }
+        Ok(Self::new(addr as *mut u8))
+    }
+
+    /// 寄存器块的基地址，用于日志和调试中区分多个 UART 实例。
+    pub fn base_addr(&self) -> usize {
+        self.base.as_ptr() as usize
+    }
+
+    /// 设置忙等循环每次迭代调用的钩子，默认为 `core::hint::spin_loop`。
+    ///
+    /// `write_blocking` 和 `flush` 在等待硬件状态变化时会反复调用该钩子，
+    /// 可以替换为 `wfi` 或协作式内核的 yield。钩子可能在关中断的上下文中被调用，
+    /// 不能阻塞等待本 UART 的中断，也不能再调用本 `Uart` 的方法。
+    pub fn set_spin_hook(&mut self, hook: fn()) {
+        self.spin_hook = hook;
+    }
+
+    /// 设置 RS-485 收发器的方向切换回调，`write_and_drain` 和 `transact` 在发送前以 `true`
+    /// 调用以使能驱动器（DE），发送完最后一个字节后以 `false` 调用切回接收。`None` 表示不需要切换（默认）。
+    pub fn set_de_hook(&mut self, hook: Option<fn(bool)>) {
+        self.de_hook = hook;
+    }
+
+    /// 一次完成 RS-485 半双工配置：设置方向切换回调 `de_cb`，并让 `write_and_drain`
+    /// （以及 `transact`）按半双工时序发送。
+    ///
+    /// 配置后空闲时只接收（TXE 关闭，DE 为 `false`）。每次发送先使能 TXE、以 `true`
+    /// 调用 `de_cb`，等最后一个字节离开移位寄存器后关闭 TXE、以 `false` 调用 `de_cb`，
+    /// 即 `flush_and_disable_tx` 的语义，不会截断最后几个字节。
+    ///
+    /// `suppress_echo` 为 `true` 时发送期间关闭接收（RXE），收发器回环回来的本节点字节
+    /// 不会进入接收 FIFO，相当于被丢弃；发送结束后重新使能接收。此期间对端发来的数据
+    /// 同样会丢失，半双工总线上这本来就是冲突。`write`、`write_blocking` 等其他发送路径
+    /// 不做方向切换。
+    #[cfg(feature = "async")]
+    pub fn configure_rs485(&mut self, de_cb: fn(bool), suppress_echo: bool) {
+        self.de_hook = Some(de_cb);
+        self.rs485 = true;
+        self.rs485_suppress_echo = suppress_echo;
+        self.set_direction(Direction::RxOnly);
+        de_cb(false);
+    }
+
+    /// 设置每次忙等（等待 BUSY 清除、发送 FIFO 清空或有空间等）的最大自旋次数，
+    /// 默认为 `u32::MAX`，相当于不限制。
+    ///
+    /// 超过上限时 `flush`、`disable`、`set_baud` 和 `write_blocking` 返回 `TimedOut`，
+    /// 不会在失效的硬件上永远挂起。
+    pub fn set_spin_cap(&mut self, cap: u32) {
+        self.busy_spin_cap = cap;
+    }
+
+    /// 设置 `init` 是否输出分频值等配置日志，默认输出。
+    ///
+    /// UART 本身作为日志输出时，可以关闭以避免初始化信息混入日志。
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// 设置 `read_line` 等控制台读取路径是否丢弃收到的 NUL（`0x00`）字节，默认不丢弃。
+    ///
+    /// 线路上的杂散 NUL 会干扰字符串处理，作为控制台时可以打开；二进制协议应保持关闭。
+    pub fn set_filter_nul(&mut self, filter: bool) {
+        self.filter_nul = filter;
+    }
+
+    // 设置奇偶校验错误的处理方式，默认为 `Deliver`
+    pub fn set_parity_error_policy(&mut self, policy: ParityErrorPolicy) {
+        self.parity_policy = policy;
+    }
+
+    // 按 `ParityErrorPolicy::Discard` 丢弃的字节数
+    pub fn parity_discarded(&self) -> usize {
+        self.parity_discarded.load(Ordering::Relaxed)
+    }
+
+    /// 设置读操作报告 `ReadError::Overrun` 前的恢复方式，默认为 `KeepBuffered`。
+    pub fn set_overrun_recovery(&mut self, recovery: OverrunRecovery) {
+        self.overrun_recovery = recovery;
+    }
+
+    // 按 `OverrunRecovery::KeepBuffered` 保留 FIFO 数据的溢出次数
+    pub fn overrun_recovered(&self) -> usize {
+        self.overrun_recovered.load(Ordering::Relaxed)
+    }
+
+    // 按 `OverrunRecovery::FlushAll` 丢弃的字节数
+    pub fn overrun_discarded(&self) -> usize {
+        self.overrun_discarded.load(Ordering::Relaxed)
+    }
+
+    // 读操作遇到溢出时按 `overrun_recovery` 处理 FIFO 中的剩余数据
+    #[cfg(feature = "async")]
+    fn recover_overrun(&self) {
+        match self.overrun_recovery {
+            OverrunRecovery::KeepBuffered => {
+                self.overrun_recovered.fetch_add(1, Ordering::Relaxed);
+            }
+            OverrunRecovery::FlushAll => {
+                // 最多读出一个 FIFO 深度，持续收到的新数据不丢弃
+                let discarded = self.rx_iter().take(self.fifo_depth()).count();
+                self.overrun_discarded
+                    .fetch_add(discarded, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// 检测寄存器地址上是否有真实的设备。
+    ///
+    /// 向 `uartibrd` 写入两个测试值并读回，两次都一致才认为设备存在，
+    /// 未接设备的地址通常读回全 0 或全 1。分频值只在写 LCRH 时生效，
+    /// 探测不影响正在使用的波特率，结束后恢复原值。
+    ///
+    /// 此外要求 `UARTPCellID0..3` 为 PrimeCell 的固定值 `0xB105F00D`。
+    pub fn probe(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        let pcell_ok = uart
+            .uartpcellid
+            .iter()
+            .zip(PRIMECELL_ID)
+            .all(|(reg, id)| reg.get() & 0xff == id as u32);
+        if !pcell_ok {
+            return false;
+        }
+        let saved = uart.uartibrd.get();
+        let present = [0xA5A5, 0x5A5A].iter().all(|&pattern| {
+            uart.uartibrd.set(pattern);
+            uart.uartibrd.get() == pattern
+        });
+        uart.uartibrd.set(saved);
+        present
+    }
+
+    /// 读取并解码外设标识寄存器，可用于按版本处理硬件差异。
+    pub fn peripheral_id(&self) -> PeripheralId {
+        let uart = unsafe { self.base.as_ref() };
+        let [id0, id1, id2, id3] = [0, 1, 2, 3].map(|i| uart.uartperiphid[i].get() & 0xff);
+        PeripheralId {
+            designer: ((id1 >> 4) | (id2 & 0xf) << 4) as u8,
+            part_number: (id0 | (id1 & 0xf) << 8) as u16,
+            revision: (id2 >> 4) as u8,
+            configuration: id3 as u8,
+        }
+    }
+
+    /// 上电自检：依次执行 `probe`、读取并校验外设标识、内部回环收发一个测试字节，
+    /// 返回各项结果。用于新板子的 bring-up，一次调用确认 UART 是否可用。
+    ///
+    /// 回环测试需要已经用 `init` 或 `set_baud` 配置过波特率，否则返回 `NotEnabled`，
+    /// 不修改任何寄存器。`probe` 失败时不做回环测试，`id_ok` 和 `loopback` 记为失败。
+    /// 测试前等待发送完成，并丢弃接收 FIFO 中的残留数据；结束后恢复全部可写寄存器，
+    /// 清除测试产生的中断状态。等待测试字节受 `set_spin_cap` 的自旋上限约束。
+    pub fn quick_check(&mut self) -> Result<QuickCheck, Error> {
+        if self.clk.load(Ordering::Relaxed) == 0 {
+            return Err(Error::NotEnabled);
+        }
+        let id = self.peripheral_id();
+        if !self.probe() {
+            return Ok(QuickCheck {
+                probe: false,
+                id,
+                id_ok: false,
+                loopback: false,
+            });
+        }
+        let uart = unsafe { self.base.as_ref() };
+        let state = self.save_state();
+        // 超时时 flush 已经输出警告，回环测试照常进行
+        let _ = self.flush();
+        critical(|| {
+            uart.uartimsc.set(0);
+            uart.uartcr
+                .write(CR::UARTEN::SET + CR::TXE::SET + CR::RXE::SET + CR::LBE::SET);
+        });
+        self.rx_iter().for_each(drop);
+        const PATTERN: u8 = 0x5a;
+        uart.uartdr.write(DATA::RAW.val(PATTERN as u32));
+        let received = self.spin_until(|| !uart.uartfr.is_set(FLAG::RXFE));
+        // 数据位一致且没有任何错误标志
+        let loopback = received && uart.uartdr.get() == PATTERN as u32;
+        self.restore_state(&state);
+        self.clear_all_interrupts();
+        Ok(QuickCheck {
+            probe: true,
+            id,
+            id_ok: id.is_pl011(),
+            loopback,
+        })
+    }
+
+    pub fn init(&self, clk: ClockHz, baud: Baud) -> Result<(), InitError> {
+        self.init_with(clk, baud, LineConfig::default())
+    }
+
+    /// 按指定帧格式初始化。
+    ///
+    /// 时钟无法产生 `baud` 时（分频值整数部分为 0 或超过 16 位）不修改任何寄存器：
+    /// debug 构建下直接 panic，便于在调试阶段发现时钟与波特率参数写反等错误，
+    /// release 构建下返回 `InitError`。
+    ///
+    /// UART 已经使能时（重复调用 `init`）会先等待发送完成再重新配置，不会截断数据。
+    /// 运行中修改配置应优先使用 `reconfigure` 或 `set_baud`。
+    ///
+    /// 配置顺序遵循 PL011 TRM（ARM DDI 0183）3.3.8 节：关闭 UART，等待 `BUSY` 清除，
+    /// 清除 FEN 冲刷 FIFO，写分频寄存器，再写 `uartlcrh`（3.3.6 节：分频值在写
+    /// `uartlcrh` 时才生效），最后重新使能。开启 `init-check` feature 后，
+    /// 写入顺序或硬件状态不符合要求时输出警告。
+    pub fn init_with(&self, clk: ClockHz, baud: Baud, line: LineConfig) -> Result<(), InitError> {
+        self.init_masked(clk, baud, line, 1 << 4 | 1 << 5)
+    }
+
+    /// 与 `init` 相同，但屏蔽所有中断，用于纯轮询的场景。
+    ///
+    /// UART 使能前中断就已屏蔽，不会在中断处理函数注册之前产生中断。
+    pub fn init_polling(&self, clk: ClockHz, baud: Baud) -> Result<(), InitError> {
+        self.init_masked(clk, baud, LineConfig::default(), 0)
+    }
+
+    /// 按控制台的常用配置初始化：115200 8N1，使能 FIFO 和收发，屏蔽所有中断。
+    ///
+    /// 返回可以直接配合 `write!` 使用的 `UartFmt`。需要其他波特率、帧格式或中断时
+    /// 使用 `init_with` 等完整的初始化接口。
+    pub fn configure_for_console(&self, clk: ClockHz) -> Result<UartFmt<'_, DEPTH, W>, InitError> {
+        self.init_polling(clk, Baud(115_200))?;
+        Ok(UartFmt(self))
+    }
+
+    // 初始化并把中断屏蔽寄存器设为 `imsc`
+    fn init_masked(
+        &self,
+        clk: ClockHz,
+        baud: Baud,
+        line: LineConfig,
+        imsc: u32,
+    ) -> Result<(), InitError> {
+        // 波特率为 0 时按过低处理，避免除零
+        let (integer_part, fraction_part) = if baud.0 == 0 {
+            (u32::MAX, 0)
+        } else {
+            baud_divisor(clk, baud)
+        };
+        if let Err(err) = check_divisor(integer_part, fraction_part) {
+            if cfg!(debug_assertions) {
+                let reason = match err {
+                    InitError::BaudTooHigh => "baud too high for clock",
+                    InitError::BaudTooLow => "baud too low for clock",
+                    InitError::InvalidFraction => "invalid baud divisor fraction",
+                };
+                panic!("{reason}: clock {} Hz, baud {}", clk.0, baud.0);
+            }
+            return Err(err);
+        }
+        let uart = unsafe { self.base.as_ref() };
+        let mut seq = InitSeq::new();
+        // 重复初始化时先等已写入的数据发完，再屏蔽中断并关闭 UART，不截断正在发送的数据
+        if uart.uartcr.is_set(CR::UARTEN) {
+            // 超时时 flush 已经输出警告，仍然继续初始化
+            let _ = self.flush();
+        }
+        seq.step(InitStep::Disable, uart);
+        critical(|| {
+            uart.uartimsc.set(0);
+            uart.uartcr.set(0);
+        });
+        // 关闭后当前字符仍可能在收发，等它结束；超时仍然继续初始化
+        seq.step(InitStep::WaitIdle, uart);
+        let _ = self.wait_busy_clear();
+        // 清除 FEN 冲刷 FIFO
+        seq.step(InitStep::FlushFifo, uart);
+        uart.uartlcrh.modify(LCRH::FEN::CLEAR);
+        // 设置波特率
+        if self.verbose {
+            info!("integer_part is {integer_part}, fraction_part is {fraction_part}");
+            // 分频值已检查过，这里不会失败
+            if let Ok(divisor) = compute_divisor(clk, baud) {
+                let ppm = divisor.error_ppm.unsigned_abs();
+                let sign = if divisor.error_ppm < 0 { "-" } else { "" };
+                debug!(
+                    "requested {}, actual {}, error {sign}{}.{:02}%",
+                    baud.0,
+                    divisor.actual.0,
+                    ppm / 10_000,
+                    ppm / 100 % 100
+                );
+            }
+        }
+        seq.step(InitStep::Divisor, uart);
+        uart.uartibrd.set(integer_part);
+        uart.uartfbrd.set(fraction_part);
+        self.clk.store(clk.0, Ordering::Relaxed);
+        self.warmup_left.store(self.warmup_bytes, Ordering::Relaxed);
+        // 配置 UART，写 uartlcrh 同时让新的分频值生效
+        if self.verbose {
+            info!("configuring UART");
+        }
+        seq.step(InitStep::LineControl, uart);
+        uart.uartlcrh.write(line.lcrh() + LCRH::FEN::SET); // 帧格式, FIFOs使能
+        self.fifo_enabled.store(true, Ordering::Relaxed);
+        // 清除上一次运行遗留的全部中断状态。热重启不会复位 uartris，
+        // 遗留的状态会让使能中断后的第一次 handle_interrupt 误处理
+        self.clear_all_interrupts();
+        self.rx_errors.store(0, Ordering::Release);
+        // 设置 FIFO 触发水位
+        uart.uartifls.set(0x20);
+        // 启用中断
+        critical(|| uart.uartimsc.set(imsc));
+        seq.step(InitStep::Enable, uart);
+        uart.uartcr
+            .write(CR::UARTEN::SET + CR::TXE::SET + CR::RXE::SET); // 使能UART, 使能接收和发送
+        Ok(())
+    }
+
+    /// 立即丢弃收发两个 FIFO 中的全部数据，用于协议复位等需要马上停止输出并重新同步的场合。
+    ///
+    /// PL011 没有单独清空发送 FIFO 的方法：只关闭 TXE 会在当前字符发完后暂停发送，
+    /// FIFO 中的数据保留，重新使能后继续发出。这里关闭 UART 后切换 FEN 同时清空两个 FIFO，
+    /// 再恢复原来的控制寄存器，因此接收 FIFO 中未读取的数据也会丢失，需要保留接收数据的
+    /// 调用者不能使用。已经在线路上的位无法收回，正在发送的最后一个字节可能不完整。
+    pub fn discard_fifos(&self) {
+        let uart = unsafe { self.base.as_ref() };
+        let cr = critical(|| {
+            let cr = uart.uartcr.get();
+            uart.uartcr.set(0);
+            cr
+        });
+        if self.fifo_enabled.load(Ordering::Relaxed) {
+            uart.uartlcrh.modify(LCRH::FEN::CLEAR);
+            uart.uartlcrh.modify(LCRH::FEN::SET);
+        }
+        uart.uartcr.set(cr);
+    }
+
+    /// 运行时开关 FIFO（`uartlcrh` 的 FEN），关闭后进入字符模式，收发各只有 1 字节的保持寄存器。
+    ///
+    /// 与其他配置修改一样先等待发送完成并关闭 UART。关闭 FIFO 会丢弃其中未读取的接收数据。
+    /// 字符模式便于精确的单字节时序测试，批量写入等路径会按当前模式决定一次写入的字节数。
+    pub fn set_fifo_enabled(&self, enable: bool) {
+        let uart = self.quiesce_for_config();
+        if enable {
+            uart.uartlcrh.modify(LCRH::FEN::SET);
+        } else {
+            uart.uartlcrh.modify(LCRH::FEN::CLEAR);
+        }
+        self.fifo_enabled.store(enable, Ordering::Relaxed);
+    }
+
+    // 当前模式下发送 FIFO 为空时可以连续写入的字节数。
+    // 字符模式下 TXFF/RXFE 反映的是 1 字节保持寄存器，只有批量路径需要区分模式。
+    fn fifo_depth(&self) -> usize {
+        if self.fifo_enabled.load(Ordering::Relaxed) {
+            DEPTH
+        } else {
+            1
+        }
+    }
+
+    /// 设置中断屏蔽寄存器，置位的中断被使能。
+    ///
+    /// 运行时修改屏蔽时，中断处理可能在修改中途进入。启用 `critical-section` feature 后
+    /// 修改在临界区内进行，不会与本核的中断处理交错；否则调用者需要自行保证。
+    /// 不关闭 UART，也不影响 FIFO 中的数据。
+    pub fn set_interrupt_mask(&self, mask: FieldValue<u32, INTERRUPT::Register>) {
+        let uart = unsafe { self.base.as_ref() };
+        critical(|| uart.uartimsc.write(mask));
+    }
+
+    /// 屏蔽本 UART 的全部中断后执行 `f`，结束后恢复原来的中断屏蔽。
+    ///
+    /// 只屏蔽本 UART 在 `uartimsc` 中的中断，不关闭 CPU 中断，其他设备的中断照常处理。
+    /// 用于执行不能被本 UART 中断处理打断的多寄存器操作。
+    pub fn with_interrupts_disabled<R>(&self, f: impl FnOnce() -> R) -> R {
+        let uart = unsafe { self.base.as_ref() };
+        // 与其他修改中断屏蔽的路径一样在临界区内读写，避免与中断中的修改交错
+        let saved = critical(|| {
+            let saved = uart.uartimsc.get();
+            uart.uartimsc.set(0);
+            saved
+        });
+        let result = f();
+        critical(|| uart.uartimsc.set(saved));
+        result
+    }
+
+    /// 设置发送和接收 FIFO 的中断触发水位。
+    ///
+    /// 发送中断在 FIFO 中的数据降到水位以下时触发，接收中断在数据达到水位时触发。
+    /// PL011 允许在使能状态下修改 `uartifls`，因此不关闭 UART，也不影响 FIFO 中的数据。
+    pub fn set_fifo_levels(&self, tx: FifoLevel, rx: FifoLevel) {
+        let uart = unsafe { self.base.as_ref() };
+        uart.uartifls
+            .write(FIFO::TXSEL.val(tx as u32) + FIFO::RXSEL.val(rx as u32));
+    }
+
+    /// 使能发送 DMA 请求（`uartdmacr` 的 TXDMAE），由外部 DMA 引擎把 `buf` 搬入发送 FIFO。
+    ///
+    /// 本驱动不控制 DMA 引擎，交接约定为：调用者先以 `buf` 为源、`base_addr()`
+    /// （`uartdr` 位于偏移 0）为目的、每次 8 位的外设传输配置好 DMA 通道，再调用本方法。
+    /// 发送 FIFO 触发水位被设为一半，FIFO 降到水位以下时 UART 发出突发传输请求，
+    /// DMA 通道的突发长度不能超过 FIFO 深度的一半。传输期间不要再用其他方式写发送 FIFO。
+    /// `buf` 为空时什么也不做。
+    pub fn start_tx_dma(&self, buf: &'static [u8]) {
+        if buf.is_empty() {
+            return;
+        }
+        let uart = unsafe { self.base.as_ref() };
+        uart.uartifls
+            .modify(FIFO::TXSEL.val(FifoLevel::Half as u32));
+        self.tx_dma_active.store(true, Ordering::Release);
+        uart.uartdmacr.modify(DMACR::TXDMAE::SET);
+    }
+
+    /// `start_tx_dma` 启动的发送是否已经完成：发送 FIFO 为空且最后一个字节已离开移位寄存器。
+    ///
+    /// 完成时关闭 TXDMAE 并返回 `true`，没有进行中的 DMA 发送时也返回 `true`。
+    /// DMA 引擎搬运的间隙 FIFO 可能短暂为空，UART 一侧无法与传输结束区分，
+    /// 因此应在 DMA 引擎报告传输完成（例如 DMA 中断）之后调用，用于确认数据已经发出。
+    pub fn tx_dma_complete(&self) -> bool {
+        if !self.tx_dma_active.load(Ordering::Acquire) {
+            return true;
+        }
+        let uart = unsafe { self.base.as_ref() };
+        let fr = uart.uartfr.extract();
+        if !fr.is_set(FLAG::TXFE) || fr.is_set(FLAG::BUSY) {
+            return false;
+        }
+        uart.uartdmacr.modify(DMACR::TXDMAE::CLEAR);
+        self.tx_dma_active.store(false, Ordering::Release);
+        true
+    }
+
+    /// 临时把发送 FIFO 触发水位设为 `level`，执行 `f` 后恢复原来的水位，接收水位不变。
+    ///
+    /// 用于大块发送时临时调整中断频率，结束后回到交互场景的设置。
+    pub fn with_tx_trigger<R>(&self, level: FifoLevel, f: impl FnOnce() -> R) -> R {
+        let uart = unsafe { self.base.as_ref() };
+        let saved = uart.uartifls.read(FIFO::TXSEL);
+        uart.uartifls.modify(FIFO::TXSEL.val(level as u32));
+        let result = f();
+        uart.uartifls.modify(FIFO::TXSEL.val(saved));
+        result
+    }
+
+    /// 暂停接收：屏蔽接收和接收超时中断，中断驱动的读取不再从接收 FIFO 取走数据。
+    ///
+    /// 用于在其上实现 XON/XOFF 等软件流控。对端在暂停期间继续发送时 FIFO 会溢出，
+    /// 之后的读取会报告 `ReadError::Overrun`。
+    pub fn pause_rx(&self) {
+        let uart = unsafe { self.base.as_ref() };
+        critical(|| {
+            uart.uartimsc
+                .modify(INTERRUPT::RXIM::CLEAR + INTERRUPT::RTIM::CLEAR)
+        });
+    }
+
+    /// 恢复接收，重新使能接收和接收超时中断。
+    pub fn resume_rx(&self) {
+        let uart = unsafe { self.base.as_ref() };
+        critical(|| {
+            uart.uartimsc
+                .modify(INTERRUPT::RXIM::SET + INTERRUPT::RTIM::SET)
+        });
+    }
+
+    /// 设置接收节流：异步读每读出 `every_bytes` 个字节就撤销 RTS，接收 FIFO 读空后
+    /// 再重新置位，让突发处理较慢的软件有时间赶上。0 表示关闭（默认）。
+    ///
+    /// 依赖对端启用 CTS 流控，否则对端不会暂停，节流没有效果。RTS 由软件控制，
+    /// 需要关闭硬件自动 RTS（`RTSEN`），否则 `uartcr` 的 RTS 位被忽略。`every_bytes`
+    /// 应小于 FIFO 深度，撤销 RTS 后对端可能还会再发一两个字节。这里不关闭接收器（RXE），
+    /// 接收器关闭期间到达的数据会直接丢失。
+    #[cfg(feature = "async")]
+    pub fn set_rx_pacing(&mut self, every_bytes: usize) {
+        self.rx_pacing = every_bytes;
+        self.rx_paced.store(0, Ordering::Relaxed);
+    }
+
+    // 读出一个字节后计数，达到节流字节数时撤销 RTS
+    #[cfg(feature = "async")]
+    fn pace_rx(&self) {
+        if self.rx_pacing == 0 {
+            return;
+        }
+        if self.rx_paced.fetch_add(1, Ordering::Relaxed) + 1 >= self.rx_pacing {
+            self.rx_paced.store(0, Ordering::Relaxed);
+            let uart = unsafe { self.base.as_ref() };
+            critical(|| uart.uartcr.modify(CR::RTS::CLEAR));
+        }
+    }
+
+    // 接收 FIFO 读空后重新置位被节流撤销的 RTS
+    #[cfg(feature = "async")]
+    fn unpace_rx(&self) {
+        let uart = unsafe { self.base.as_ref() };
+        if self.rx_pacing != 0 && !uart.uartcr.is_set(CR::RTS) {
+            critical(|| uart.uartcr.modify(CR::RTS::SET));
+        }
+    }
+
+    /// 使能异步读写所需的全部中断：接收、接收超时和发送。
+    ///
+    /// 只开接收中断而不开接收超时中断时，不足 FIFO 触发水位的数据不会产生中断，
+    /// 读操作会一直挂起。
+    pub fn configure_interrupts_for_async(&self) {
+        self.set_interrupt_mask(INTERRUPT::RXIM::SET + INTERRUPT::RTIM::SET + INTERRUPT::TXIM::SET);
+    }
+
+    /// 设置 `set_baud` 是否清空接收 FIFO，默认清空。
+    ///
+    /// 按旧波特率收到的数据在新波特率下通常是乱码，清空可以避免它们混入之后的读取；
+    /// 需要保留切换前数据的调用者应关闭该选项，并在 `set_baud` 之前读出数据。
+    pub fn set_clear_rx_on_baud_change(&mut self, clear: bool) {
+        self.clear_rx_on_baud_change = clear;
+    }
+
+    /// 设置 `init`/`set_baud` 之后读路径丢弃的字节数，默认为 0。
+    ///
+    /// 用于规避部分板卡的硬件问题：使能 UART 或修改波特率后波特率时钟尚未稳定，
+    /// 最先收到的一两个字节是乱码。丢弃的字节不论是否带错误标志都会计数，
+    /// 新的设置从下一次 `init`/`set_baud` 开始生效。除原始读取的 `rx_iter` 外，
+    /// 各个读路径都会丢弃预热字节。
+    pub fn set_warmup_bytes(&mut self, n: u8) {
+        self.warmup_bytes = n;
+    }
+
+    /// 半双工接线时丢弃本节点发送后又被自己接收到的回显字节，默认关闭。
+    ///
+    /// 使能后每个写入发送 FIFO 的字节都会计数，接收路径（`read`、`receive_checked`、
+    /// `try_receive`、`try_read`、`receive`、`nb` 读取等）先丢弃同样数量的字节再交付数据，
+    /// 回显晚于发送完成到达也能正确丢弃。原始读取的 `rx_iter` 不丢弃。适用于接收器一直开着、收发器把发送回环到 RX 的接线；
+    /// `configure_rs485(.., true)` 发送期间已经关闭接收，不会产生回显，不要同时使能。
+    ///
+    /// 回显字节丢失（例如总线冲突）时计数会错位，每次调用都会清零计数。
+    /// `start_tx_dma` 发送的数据不计数。
+    pub fn set_suppress_tx_echo(&mut self, enable: bool) {
+        self.suppress_tx_echo = enable;
+        self.tx_echo_pending.store(0, Ordering::Relaxed);
+    }
+
+    // 写入发送 FIFO 的字节数，回显抑制开启时记入待丢弃的回显
+    pub(crate) fn note_tx(&self, n: usize) {
+        if self.suppress_tx_echo && n > 0 {
+            self.tx_echo_pending.fetch_add(n, Ordering::Relaxed);
+        }
+    }
+
+    // 收到的字节属于预热期或本节点的回显、需要丢弃时返回 true，同时对应计数减一
+    pub(crate) fn take_discard(&self) -> bool {
+        if self
+            .warmup_left
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            return true;
+        }
+        self.suppress_tx_echo
+            && self
+                .tx_echo_pending
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok()
+    }
+
+    /// 结合当前配置和中断屏蔽检查容易导致停滞的组合，返回发现的第一个问题。
+    ///
+    /// 这些只是警告，某些场合（例如只用轮询读取接收数据）可能是有意为之，由调用者决定如何处理。
+    pub fn validate_config(&self) -> Result<(), ConfigWarning> {
+        let uart = unsafe { self.base.as_ref() };
+        let cfg = self.current_config();
+        let imsc = uart.uartimsc.extract();
+        let rx_irq = imsc.is_set(INTERRUPT::RXIM) || imsc.is_set(INTERRUPT::RTIM);
+        // 字符模式下每个字节都会触发接收中断，不需要超时中断
+        if imsc.is_set(INTERRUPT::RXIM) && !imsc.is_set(INTERRUPT::RTIM) && cfg.fifo_enabled {
+            return Err(ConfigWarning::RxTriggerWithoutTimeout);
+        }
+        if rx_irq && !(cfg.uart_enabled && cfg.rx_enabled) {
+            return Err(ConfigWarning::RxInterruptWithReceiverDisabled);
+        }
+        if imsc.is_set(INTERRUPT::TXIM) && !(cfg.uart_enabled && cfg.tx_enabled) {
+            return Err(ConfigWarning::TxInterruptWithTransmitterDisabled);
+        }
+        Ok(())
+    }
+
+    /// 从 `uartlcrh`、`uartcr` 和分频寄存器读回当前配置。
+    ///
+    /// 停止位置位时总是解码为 `StopBits::Two`，`OneAndHalf` 只是它在 5 位数据下的别名。
+    pub fn current_config(&self) -> UartConfig {
+        let uart = unsafe { self.base.as_ref() };
+        let lcrh = uart.uartlcrh.extract();
+        let cr = uart.uartcr.extract();
+        let data_bits = match lcrh.read_as_enum(LCRH::WLEN) {
+            Some(LCRH::WLEN::Value::Five) => DataBits::Five,
+            Some(LCRH::WLEN::Value::Six) => DataBits::Six,
+            Some(LCRH::WLEN::Value::Seven) => DataBits::Seven,
+            _ => DataBits::Eight,
+        };
+        let parity = if !lcrh.is_set(LCRH::PEN) {
+            Parity::None
+        } else if lcrh.is_set(LCRH::EPS) {
+            Parity::Even
+        } else {
+            Parity::Odd
+        };
+        let stop_bits = if lcrh.is_set(LCRH::STP2) {
+            StopBits::Two
+        } else {
+            StopBits::One
+        };
+        let flow_control = self.flow_control();
+        UartConfig {
+            line: LineConfig {
+                data_bits,
+                parity,
+                stop_bits,
+            },
+            fifo_enabled: lcrh.is_set(LCRH::FEN),
+            uart_enabled: cr.is_set(CR::UARTEN),
+            tx_enabled: cr.is_set(CR::TXE),
+            rx_enabled: cr.is_set(CR::RXE),
+            flow_control,
+            divisor: (uart.uartibrd.get(), uart.uartfbrd.get()),
+        }
+    }
+
+    /// 从 `uartcr` 的 RTSEN/CTSEN 读回当前的硬件流控模式。
+    pub fn flow_control(&self) -> FlowControl {
+        let uart = unsafe { self.base.as_ref() };
+        FlowControl::from_cr(uart.uartcr.extract())
+    }
+
+    /// 设置硬件流控模式，与其他配置修改一样先等待发送完成并关闭 UART。
+    pub fn set_flow_control(&self, flow: FlowControl) {
+        let mut uart = self.quiesce_for_config();
+        let mut cr = LocalRegisterCopy::<u32, CR::Register>::new(uart.cr);
+        cr.modify(flow.cr_fields());
+        // guard 销毁时写回新的控制寄存器
+        uart.cr = cr.get();
+    }
+
+    /// 读回 `uartcr` 中的控制位。
+    pub fn control(&self) -> ControlConfig {
+        let uart = unsafe { self.base.as_ref() };
+        let cr = uart.uartcr.extract();
+        ControlConfig {
+            uart_enable: cr.is_set(CR::UARTEN),
+            tx_enable: cr.is_set(CR::TXE),
+            rx_enable: cr.is_set(CR::RXE),
+            rts: cr.is_set(CR::RTS),
+            loopback: cr.is_set(CR::LBE),
+            flow_control: FlowControl::from_cr(cr),
+        }
+    }
+
+    /// 按 `config` 写入 `uartcr`，`ControlConfig` 之外的控制位（DTR、OUT1/OUT2、IrDA 等）保持不变。
+    ///
+    /// 与其他配置修改一样先等待发送完成并关闭 UART，再一次写入新的控制寄存器，
+    /// 不会在 UART 使能期间修改收发和回环设置。
+    pub fn set_control(&self, config: ControlConfig) {
+        let bit = |enable: bool| enable as u32;
+        let mut uart = self.quiesce_for_config();
+        let mut cr = LocalRegisterCopy::<u32, CR::Register>::new(uart.cr);
+        cr.modify(
+            CR::UARTEN.val(bit(config.uart_enable))
+                + CR::TXE.val(bit(config.tx_enable))
+                + CR::RXE.val(bit(config.rx_enable))
+                + CR::RTS.val(bit(config.rts))
+                + CR::LBE.val(bit(config.loopback))
+                + config.flow_control.cr_fields(),
+        );
+        // guard 销毁时写回新的控制寄存器，此前 UART 保持关闭
+        uart.cr = cr.get();
+    }
+
+    /// 读回 `uartibrd`/`uartfbrd` 中的分频值 `(整数部分, 小数部分)`。
+    ///
+    /// 可以与 `baud_divisor` 的结果比较，确认初始化时的写入确实生效，配合 `probe`
+    /// 检查 UART 是否存在且已正确配置。
+    pub fn divisor(&self) -> (u16, u8) {
+        let uart = unsafe { self.base.as_ref() };
+        (
+            uart.uartibrd.get() as u16,
+            (uart.uartfbrd.get() & 0x3f) as u8,
+        )
+    }
+
+    /// 等待发送完成后修改波特率。
+    ///
+    /// 时钟无法产生 `baud` 时返回 `Error::InvalidBaud`，例如 100 MHz 时钟下 75 波特
+    /// 需要约 83333 的整数分频值，超出 `uartibrd` 的 16 位，写入会被截断成完全不同的波特率。
+    /// 等待发送完成超时时返回 `Error::TimedOut`。两种情况都不修改任何寄存器。
+    pub fn set_baud(&self, clk: ClockHz, baud: Baud) -> Result<(), Error> {
+        let divisor = compute_divisor(clk, baud)?;
+        self.write_divisor(divisor.integer, divisor.fraction)?;
+        self.clk.store(clk.0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 绕过波特率公式，直接把分频值写入 `uartibrd`/`uartfbrd`。
+    ///
+    /// 用于时钟树的实际行为与标称频率不符、按公式算出的分频值不对的场合，
+    /// 调用者自行保证分频值对应期望的波特率。关闭和重新使能 UART 的顺序及
+    /// 写 `uartlcrh` 使分频值生效的步骤与 `set_baud` 相同。`fraction` 超过 63、
+    /// `integer` 为 0，或 `integer` 为 0xFFFF 而 `fraction` 不为 0（TRM 不允许）时
+    /// 返回 `Error::InvalidBaud`，等待发送完成超时时返回
+    /// `Error::TimedOut`，两种情况都不修改任何寄存器。`bits_to_ns` 等换算仍按
+    /// 上一次 `init`/`set_baud` 的时钟进行。
+    pub fn set_divisor(&self, integer: u16, fraction: u8) -> Result<(), Error> {
+        check_divisor(integer as u32, fraction as u32)?;
+        self.write_divisor(integer as u32, fraction as u32)
+    }
+
+    // 等待发送完成后关闭 UART 写入分频值，并按 `set_baud` 的约定处理接收状态
+    fn write_divisor(&self, integer: u32, fraction: u32) -> Result<(), Error> {
+        self.flush()?;
+        let uart = self.quiesce_for_config();
+        uart.uartibrd.set(integer);
+        uart.uartfbrd.set(fraction);
+        self.warmup_left.store(self.warmup_bytes, Ordering::Relaxed);
+        // 分频值在写 LCRH 时才会生效
+        uart.uartlcrh.set(uart.uartlcrh.get());
+        if self.clear_rx_on_baud_change {
+            self.rx_iter().for_each(drop);
+            self.rx_errors.store(0, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// 查询 `clk` 的当前频率后修改波特率，语义同 `set_baud`。
+    ///
+    /// 参考时钟可能被动态调整时，避免按写死的频率计算分频值导致波特率漂移。
+    pub fn set_baud_from(&self, clk: &impl ClockProvider, baud: Baud) -> Result<(), Error> {
+        self.set_baud(clk.uart_clk_hz(), baud)
+    }
+
+    /// 对端持续发送同步字节 `0x55` 时，依次尝试 `candidates` 中的波特率，
+    /// 返回第一个能无错误地收到同步字节的波特率，最后停留在该波特率上。
+    ///
+    /// 每个候选值监听约 20 帧的时间，期间至少收到 2 个 `0x55`
+    /// 且没有帧错误等接收错误才算匹配。时钟无法产生的候选值被跳过。
+    /// `now_ns` 是调用者提供的单调纳秒时钟。都不匹配时返回 `None`，波特率停留在最后一个候选值。
+    pub fn autobaud(
+        &mut self,
+        candidates: &[Baud],
+        clk: ClockHz,
+        now_ns: impl Fn() -> u64,
+    ) -> Option<Baud> {
+        const AUTOBAUD_FRAMES: u64 = 20;
+        for &baud in candidates {
+            if self.set_baud(clk, baud).is_err() {
+                continue;
+            }
+            // 丢弃切换前按旧波特率收到的数据
+            self.rx_iter().for_each(drop);
+            let window = AUTOBAUD_FRAMES * 10 * 1_000_000_000 / baud.0 as u64;
+            let start = now_ns();
+            let mut good = 0;
+            let mut clean = true;
+            while now_ns().saturating_sub(start) < window {
+                match self.receive_checked() {
+                    Some(Ok(0x55)) => good += 1,
+                    Some(_) => {
+                        clean = false;
+                        break;
+                    }
+                    None => (self.spin_hook)(),
+                }
+            }
+            if clean && good >= 2 {
+                return Some(baud);
+            }
+        }
+        None
+    }
+
+    /// 运行时修改数据位宽，校验位和停止位保持不变。
+    ///
+    /// 与 `set_baud` 一样先等待发送完成并关闭 UART，写入 WLEN 后恢复原来的状态。
+    /// 位宽不足 8 位时接收数据的高位由硬件清零，读取路径不需要额外的掩码。
+    pub fn set_word_length(&self, bits: DataBits) {
+        let uart = self.quiesce_for_config();
+        uart.uartlcrh.modify(bits.wlen());
+    }
+
+    /// 按连线情况只使能发送、只使能接收或两者都使能（`uartcr` 的 TXE/RXE）。
+    ///
+    /// 与其他配置修改一样先等待发送完成并关闭 UART。需要在类型上排除未连接的方向时，
+    /// 使用 `into_tx_only` 或 `into_rx_only`。
+    pub fn set_direction(&self, direction: Direction) {
+        let (tx, rx) = match direction {
+            Direction::TxOnly => (1, 0),
+            Direction::RxOnly => (0, 1),
+            Direction::Both => (1, 1),
+        };
+        let mut uart = self.quiesce_for_config();
+        let mut cr = LocalRegisterCopy::<u32, CR::Register>::new(uart.cr);
+        cr.modify(CR::TXE.val(tx) + CR::RXE.val(rx));
+        // guard 销毁时写回新的控制寄存器
+        uart.cr = cr.get();
+    }
+
+    /// 只使能发送，返回只有发送方法的 `UartTx`。
+    pub fn into_tx_only(self) -> UartTx<DEPTH, W> {
+        self.set_direction(Direction::TxOnly);
+        UartTx {
+            uart: Arc::new(self),
+        }
+    }
+
+    /// 只使能接收，返回只有接收方法的 `UartRx`。
+    pub fn into_rx_only(self) -> UartRx<DEPTH, W> {
+        self.set_direction(Direction::RxOnly);
+        UartRx {
+            uart: Arc::new(self),
+        }
+    }
+
+    /// 在一次关闭→写入→使能的过程中应用 `cfg` 中的全部设置。
+    ///
+    /// 相比分别调用 `set_baud`、`set_word_length` 等方法，UART 只关闭一次，
+    /// 也不会出现只改了一部分的中间状态。`cfg` 通常来自修改过的 `current_config`，
+    /// 回环等 `UartConfig` 不包含的控制位保持不变。分频值无效时不做任何修改：
+    /// 小数部分超过 63 返回 `InvalidFraction`，超过 0xFFFF 的分频值返回 `BaudTooLow`，
+    /// 与 `set_divisor` 一样不会截断成另一个波特率。
+    pub fn reconfigure(&self, cfg: UartConfig) -> Result<(), InitError> {
+        let (integer_part, fraction_part) = cfg.divisor;
+        check_divisor(integer_part, fraction_part)?;
+        let mut uart = self.quiesce_for_config();
+        uart.uartibrd.set(integer_part);
+        uart.uartfbrd.set(fraction_part);
+        // 写 LCRH 使分频值生效
+        let fen = if cfg.fifo_enabled {
+            LCRH::FEN::SET
+        } else {
+            LCRH::FEN::CLEAR
+        };
+        uart.uartlcrh.write(cfg.line.lcrh() + fen);
+        self.fifo_enabled.store(cfg.fifo_enabled, Ordering::Relaxed);
+        let mut cr = LocalRegisterCopy::<u32, CR::Register>::new(uart.cr);
+        cr.modify(
+            CR::UARTEN.val(cfg.uart_enabled as u32)
+                + CR::TXE.val(cfg.tx_enabled as u32)
+                + CR::RXE.val(cfg.rx_enabled as u32)
+                + cfg.flow_control.cr_fields(),
+        );
+        // guard 销毁时写回新的控制寄存器
+        uart.cr = cr.get();
+        Ok(())
+    }
+
+    /// 保存全部可写寄存器，用于 UART 断电前的挂起。
+    ///
+    /// 只按名字读取已定义的寄存器，不会访问 `UartRegs` 中的保留区域。
+    pub fn save_state(&self) -> SavedState {
+        let uart = unsafe { self.base.as_ref() };
+        SavedState {
+            ibrd: uart.uartibrd.get(),
+            fbrd: uart.uartfbrd.get(),
+            lcrh: uart.uartlcrh.get(),
+            cr: uart.uartcr.get(),
+            ifls: uart.uartifls.get(),
+            imsc: uart.uartimsc.get(),
+            dmacr: uart.uartdmacr.get(),
+        }
+    }
+
+    /// 恢复 `save_state` 保存的寄存器。
+    ///
+    /// 先关闭 UART，写入分频值后写 LCRH 使其生效，再恢复 FIFO 水位、中断屏蔽和 DMA 控制，
+    /// 最后写回控制寄存器重新使能 UART。
+    pub fn restore_state(&self, state: &SavedState) {
+        let uart = unsafe { self.base.as_ref() };
+        uart.uartcr.set(0);
+        uart.uartibrd.set(state.ibrd);
+        uart.uartfbrd.set(state.fbrd);
+        uart.uartlcrh.set(state.lcrh);
+        self.fifo_enabled.store(
+            LocalRegisterCopy::<u32, LCRH::Register>::new(state.lcrh).is_set(LCRH::FEN),
+            Ordering::Relaxed,
+        );
+        uart.uartifls.set(state.ifls);
+        // 与中断中对 TXIM 的读改写互斥
+        critical(|| uart.uartimsc.set(state.imsc));
+        uart.uartdmacr.set(state.dmacr);
+        uart.uartcr.set(state.cr);
+    }
+
+    /// 等待发送完成后关闭 UART 并屏蔽中断，返回可以修改寄存器的 `ConfigGuard`。
+    ///
+    /// PL011 要求修改帧格式、分频值等配置前先关闭 UART。guard 销毁时恢复原来的
+    /// 中断屏蔽和控制寄存器，重新使能 UART。
+    ///
+    /// 修改波特率（`set_baud`）、帧格式（`set_word_length` 等）和控制寄存器（`set_control`、
+    /// `set_flow_control`）走这条路径；修改中断屏蔽（`set_interrupt_mask`）和 FIFO 水位
+    /// （`set_fifo_levels`）不需要关闭 UART，直接写寄存器，不等待发送完成。
+    pub fn quiesce_for_config(&self) -> ConfigGuard<'_> {
+        let uart = unsafe { self.base.as_ref() };
+        // 超时时 flush 已经输出警告，需要区分的调用者先自行调用 flush
+        let _ = self.flush();
+        let (cr, imsc) = critical(|| {
+            let saved = (uart.uartcr.get(), uart.uartimsc.get());
+            uart.uartcr.set(0);
+            uart.uartimsc.set(0);
+            saved
+        });
+        ConfigGuard {
+            regs: uart,
+            cr,
+            imsc,
+        }
+    }
+
+    // 发送数据
+    #[cfg(feature = "async")]
+    pub fn write<'a>(&'a mut self, data: &'a [u8]) -> impl Future<Output = usize> + 'a {
+        WriteFuture {
+            uart: self,
+            data,
+            index: 0,
+            cooperative: false,
+            completed: false,
+        }
+    }
+
+    /// 发送 `count` 个 `byte`，返回发送的字节数，用于填充图案、清屏等场合。
+    ///
+    /// 只在栈上用一个 FIFO 深度的小块作为数据源，不需要准备 `count` 字节的缓冲区。
+    #[cfg(feature = "async")]
+    pub async fn write_repeated(&mut self, byte: u8, count: usize) -> usize {
+        let chunk = [byte; DEPTH];
+        let mut sent = 0;
+        while sent < count {
+            let len = (count - sent).min(DEPTH);
+            sent += self.write(&chunk[..len]).await;
+        }
+        sent
+    }
+
+    /// 把收到的字节原样发回，直到回显了 `max_bytes` 个字节，返回回显的字节数。
+    ///
+    /// 用于板级调试时同时验证收发两个方向和中断。带接收错误的字节不回显。
+    #[cfg(feature = "async")]
+    pub async fn echo_loop(&mut self, max_bytes: usize) -> usize {
+        let mut echoed = 0;
+        while echoed < max_bytes {
+            let mut byte = [0];
+            if self.read(&mut byte).await.is_err() {
+                continue;
+            }
+            self.write(&byte).await;
+            echoed += 1;
+        }
+        echoed
+    }
+
+    /// 与 `write` 相同，但在启用 CTS 流控时，对端撤销 CTS 连续超过 `timeout_bits` 个位时间
+    /// 则放弃发送，返回 `WriteError::FlowControlTimeout`。
+    ///
+    /// CTS 撤销时 FIFO 不会排空，也不会产生发送中断，`write` 会永远挂起。
+    /// 等待 CTS 期间本 future 会不断重新调度自己以检查时间。未启用 CTS 流控时与 `write` 相同。
+    #[cfg(feature = "async")]
+    pub fn write_with_cts_timeout<'a>(
+        &'a mut self,
+        data: &'a [u8],
+        timeout_bits: u32,
+    ) -> impl Future<Output = Result<usize, WriteError>> + 'a {
+        CtsWriteFuture {
+            inner: WriteFuture {
+                uart: self,
+                data,
+                index: 0,
+                cooperative: false,
+                completed: false,
+            },
+            timeout_bits,
+            deadline: None,
+        }
+    }
+
+    /// 与 `write` 相同，但每填满一次发送 FIFO 就返回 `Pending` 并立即唤醒自己，
+    /// 让同一个协作式执行器上的其他任务有机会运行。
+    ///
+    /// `write` 在 FIFO 一直有空间时会连续发完整个切片才让出；这里以更多的轮询次数、
+    /// 可能更低的吞吐量换取公平性，其他任务运行较久时 FIFO 可能短暂排空。
+    #[cfg(feature = "async")]
+    pub fn write_cooperative<'a>(&'a mut self, data: &'a [u8]) -> impl Future<Output = usize> + 'a {
+        WriteFuture {
+            uart: self,
+            data,
+            index: 0,
+            cooperative: true,
+            completed: false,
+        }
+    }
+
+    /// 与 `write` 相同，但每次 poll 前检查 `cancel`，置位后立即完成，返回已写入发送 FIFO 的字节数。
+    ///
+    /// 用于在 Ctrl-C 等场合中止长输出。`cancel` 由其他上下文置位时不会唤醒本 future，
+    /// 取消在下一次 poll（最迟在下一次发送中断）时生效。已经进入 FIFO 的字节仍会发出，
+    /// 需要立即停止输出时再调用 `discard_fifos`（同时丢弃接收 FIFO 中的数据）。
+    #[cfg(feature = "async")]
+    pub fn write_cancellable<'a>(
+        &'a mut self,
+        data: &'a [u8],
+        cancel: &'a AtomicBool,
+    ) -> impl Future<Output = usize> + 'a {
+        CancellableWriteFuture {
+            inner: WriteFuture {
+                uart: self,
+                data,
+                index: 0,
+                cooperative: false,
+                completed: false,
+            },
+            cancel,
+        }
+    }
+
+    /// 发送数据，并在最后一个字节离开移位寄存器（`BUSY` 清除）后才完成。
+    ///
+    /// `write` 在数据全部进入发送 FIFO 时就完成，此时数据可能还没有发到线路上。
+    /// RS-485 等半双工总线需要在发送结束后切换方向，必须使用本方法，
+    /// 否则会截断最后几个字节并与对端的应答冲突。等待 `BUSY` 没有对应的中断，
+    /// 期间 future 每次 poll 都会立即重新唤醒自己。
+    ///
+    /// 设置了 `set_de_hook` 时在发送前后切换收发器方向；`configure_rs485` 之后
+    /// 还会按半双工时序切换 TXE/RXE，见 `configure_rs485`。
+    #[cfg(feature = "async")]
+    pub async fn write_and_drain(&mut self, data: &[u8]) -> usize {
+        if self.rs485 {
+            self.set_direction(if self.rs485_suppress_echo {
+                Direction::TxOnly
+            } else {
+                Direction::Both
+            });
+        }
+        if let Some(hook) = self.de_hook {
+            hook(true);
+        }
+        self.write_preamble().await;
+        let written = self.write(data).await;
+        if self.rs485 {
+            // 关闭 TXE 并回调 DE，之后恢复只接收
+            self.flush_and_disable_tx().await;
+            self.set_direction(Direction::RxOnly);
+        } else {
+            DrainFuture { uart: self }.await;
+            if let Some(hook) = self.de_hook {
+                hook(false);
+            }
+        }
+        written
+    }
+
+    /// 多点总线上释放总线：等待最后一个字节离开移位寄存器（`BUSY` 清除）后关闭发送（TXE）。
+    ///
+    /// 设置了 `set_de_hook` 时随后以 `false` 调用，关闭收发器的驱动器，
+    /// 保证本节点在最后一位发到线路上之后才停止驱动总线。接收保持不变，
+    /// 重新发送前需要用 `set_direction` 或 `set_control` 使能发送。
+    #[cfg(feature = "async")]
+    pub async fn flush_and_disable_tx(&self) {
+        DrainFuture { uart: self }.await;
+        {
+            let mut uart = self.quiesce_for_config();
+            let mut cr = LocalRegisterCopy::<u32, CR::Register>::new(uart.cr);
+            cr.modify(CR::TXE::CLEAR);
+            // guard 销毁时写回新的控制寄存器
+            uart.cr = cr.get();
+        }
+        if let Some(hook) = self.de_hook {
+            hook(false);
+        }
+    }
+
+    /// 半双工请求/应答：发送 `req` 并等待最后一个字节发出，然后接收应答写入 `resp`，
+    /// 直到线路空闲 `idle_bits` 个位时间或 `resp` 写满，返回应答长度。
+    ///
+    /// 设置了 `set_de_hook` 时在发送前后切换收发器方向。空闲从请求发送完成开始计时，
+    /// 每收到一个字节重新计时，对端一直不应答时返回 `Ok(0)`。计时使用通用定时器，
+    /// 等待期间 future 每次 poll 都会立即重新唤醒自己。
+    #[cfg(feature = "async")]
+    pub async fn transact(
+        &mut self,
+        req: &[u8],
+        resp: &mut [u8],
+        idle_bits: u32,
+    ) -> Result<usize, Error> {
+        self.write_and_drain(req).await;
+        let mut len = 0;
+        let mut deadline = CNTPCT_EL0.get() + self.bit_ticks(idle_bits);
+        core::future::poll_fn(|cx| {
+            while len < resp.len() {
+                match self.receive_checked() {
+                    Some(Ok(byte)) => {
+                        resp[len] = byte;
+                        len += 1;
+                        deadline = CNTPCT_EL0.get() + self.bit_ticks(idle_bits);
+                    }
+                    Some(Err(err)) => return Poll::Ready(Err(err.into())),
+                    None => break,
+                }
+            }
+            if len == resp.len() || CNTPCT_EL0.get() >= deadline {
+                return Poll::Ready(Ok(len));
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// 设置每次发送前先发出的前导码，默认为空。
+    ///
+    /// 用于需要固定唤醒序列或交替位（如 `0x55`）来锁定时钟恢复的接收方。前导码在
+    /// `write_vectored`、`write_line`、`write_and_drain`、`transact` 和 `write_slip_frame`
+    /// 的开头发出，直接从 `bytes` 发送，不拷贝；`write` 等字节流接口不受影响。
+    /// COBS 帧以 0 字节分隔，前导码会被接收方当作帧内容，`write_cobs_frame` 不发送前导码。
+    pub fn set_preamble(&mut self, bytes: &'static [u8]) {
+        self.preamble = bytes;
+    }
+
+    // 发送 `set_preamble` 设置的前导码
+    #[cfg(feature = "async")]
+    pub(crate) async fn write_preamble(&mut self) {
+        let preamble = self.preamble;
+        if !preamble.is_empty() {
+            self.write(preamble).await;
+        }
+    }
+
+    /// 按顺序发送多个缓冲区，作为一次逻辑发送，返回发送的总字节数。
+    ///
+    /// 帧头、负载和校验分开存放时不必先拷贝到同一个缓冲区，空缓冲区会被跳过。
+    /// 设置了 `set_preamble` 时先发送前导码，返回值不计前导码。
+    #[cfg(feature = "async")]
+    pub async fn write_vectored(&mut self, bufs: &[&[u8]]) -> usize {
+        self.write_preamble().await;
+        let mut written = 0;
+        for buf in bufs.iter().filter(|buf| !buf.is_empty()) {
+            written += self.write(buf).await;
+        }
+        written
+    }
+
+    /// 发送字符串并追加 `\r\n`，返回发送的总字节数，空字符串只发送行尾。
+    #[cfg(feature = "async")]
+    pub async fn write_line(&mut self, s: &str) -> usize {
+        self.write_vectored(&[s.as_bytes(), b"\r\n"]).await
+    }
+
+    /// 返回可以反复发送的 `Transfer`，在循环中发送小块数据时不必每次重新借用 `Uart`。
+    #[cfg(feature = "async")]
+    pub fn transfer(&mut self) -> Transfer<'_, DEPTH, W> {
+        Transfer { uart: self }
+    }
+
+    /// 异步发送字符串。
+    ///
+    /// 按原顺序发送 UTF-8 字节，不会重排，多字节字符在线路上保持完整。
+    #[cfg(feature = "async")]
+    pub fn write_str<'a>(&'a mut self, s: &'a str) -> impl Future<Output = usize> + 'a {
+        self.write(s.as_bytes())
+    }
+
+    /// 按字符边界分块异步发送字符串，每块不超过 FIFO 深度（单个字符超长时除外）。
+    ///
+    /// 每块发送完成后以该块调用 `on_chunk`，适合需要逐块记录日志的场景。
+    #[cfg(feature = "async")]
+    pub async fn write_str_chunked(&mut self, s: &str, mut on_chunk: impl FnMut(&str)) -> usize {
+        let mut written = 0;
+        for chunk in str_chunks(s, DEPTH) {
+            written += self.write(chunk.as_bytes()).await;
+            on_chunk(chunk);
+        }
+        written
+    }
+
+    /// 由中断驱动发送 `data`，最后一个字节发到线路上（`TXFE` 且 `BUSY` 清除）后
+    /// 在 `handle_interrupt` 中调用 `on_done`。
+    ///
+    /// 供不使用 executor、完全由中断驱动的代码使用。这里只把数据预先填入 FIFO 并使能发送
+    /// 中断（TXIM），剩余数据和完成回调都在 `handle_interrupt` 中处理，即使 `data` 能一次
+    /// 放进 FIFO（包括空切片）也不会在调用者的上下文中回调。最后一个字节进入 FIFO 后
+    /// TXIM 立即恢复为调用前的状态，发送中断照常清除，不会在等待发送器空闲期间反复触发。
+    ///
+    /// PL011 没有发送器空闲中断，完成检查在之后的 `handle_interrupt` 中进行，由任何中断源
+    /// （接收、接收超时等）触发，`disable` 也会完成它。因此回调相对最后一个字节离开线路
+    /// 至少晚一个字符时间，没有其他中断时会一直推迟到下一次中断。
+    ///
+    /// `data` 在中断中被读取，因此必须是 `'static`。`on_done` 在中断上下文中以发送字节数
+    /// 调用，不能阻塞，也不能再次调用本 `Uart` 的 `write_async_cb`。
+    /// 已有未完成的回调发送时返回 `false`，不会打断原有发送。
+    pub fn write_async_cb(&self, data: &'static [u8], on_done: fn(usize)) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        let mut cb_write = self.cb_write.lock();
+        if cb_write.is_some() {
+            return false;
+        }
+        let txim_was_set = critical(|| {
+            let set = uart.uartimsc.is_set(INTERRUPT::TXIM);
+            uart.uartimsc.modify(INTERRUPT::TXIM::SET);
+            set
+        });
+        *cb_write = Some(CallbackWrite {
+            data,
+            index: 0,
+            on_done,
+            txim_was_set,
+            txim_armed: true,
+        });
+        drop(cb_write);
+        self.fill_cb_write();
+        true
+    }
+
+    // 将回调发送的数据填入 FIFO，没有回调发送或数据已经全部进入 FIFO 时返回 true。
+    // 全部进入 FIFO 后不再需要发送中断，恢复 TXIM
+    fn fill_cb_write(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        let mut cb_write = self.cb_write.lock();
+        let Some(transfer) = cb_write.as_mut() else {
+            return true;
+        };
+        while transfer.index < transfer.data.len() && !uart.uartfr.is_set(FLAG::TXFF) {
+            uart.uartdr
+                .write(DATA::RAW.val(transfer.data[transfer.index] as u32));
+            transfer.index += 1;
+            self.note_tx(1);
+        }
+        let queued = transfer.index == transfer.data.len();
+        if queued && transfer.txim_armed {
+            transfer.txim_armed = false;
+            if !transfer.txim_was_set {
+                critical(|| uart.uartimsc.modify(INTERRUPT::TXIM::CLEAR));
+            }
+        }
+        queued
+    }
+
+    // 数据全部进入 FIFO 且发送器空闲时结束回调发送并调用回调
+    fn finish_cb_write(&self) {
+        let uart = unsafe { self.base.as_ref() };
+        let mut cb_write = self.cb_write.lock();
+        let Some(transfer) = cb_write.as_ref() else {
+            return;
+        };
+        let fr = uart.uartfr.extract();
+        if transfer.txim_armed || !fr.is_set(FLAG::TXFE) || fr.is_set(FLAG::BUSY) {
+            return;
+        }
+        let transfer = cb_write.take().unwrap();
+        drop(cb_write);
+        (transfer.on_done)(transfer.index);
+    }
+
+    // 中断中推进回调发送
+    fn pump_cb_write(&self) {
+        self.fill_cb_write();
+        self.finish_cb_write();
+    }
+
+    /// 登记接收缓冲区，由 `handle_interrupt` 直接把接收 FIFO 中的数据读入 `buf`。
+    ///
+    /// `buf` 填满，或发生接收超时中断且已经收到数据时，在中断上下文中以缓冲区和
+    /// 收到的字节数调用 `on_done`，缓冲区的所有权随之交还调用者。省去中间环形缓冲区的
+    /// 拷贝，适合高速数据流，需要使能接收和接收超时中断（见 `configure_interrupts_for_async`）。
+    /// `buf` 在中断中被写入，因此必须是 `'static`。`on_done` 不能阻塞，可以再次调用
+    /// `arm_rx` 登记下一个缓冲区以实现双缓冲。缓冲区登记期间接收数据不会交给 `read`。
+    /// 已有登记的缓冲区时返回 `false`。
+    pub fn arm_rx(&self, buf: &'static mut [u8], on_done: fn(&'static mut [u8], usize)) -> bool {
+        let mut cb_read = self.cb_read.lock();
+        if cb_read.is_some() {
+            return false;
+        }
+        *cb_read = Some(CallbackRead {
+            buf,
+            index: 0,
+            on_done,
+        });
+        true
+    }
+
+    // 把接收 FIFO 中的数据读入登记的缓冲区，填满或超时后调用回调
+    fn pump_cb_read(&self, timeout: bool) {
+        let uart = unsafe { self.base.as_ref() };
+        let mut cb_read = self.cb_read.lock();
+        let Some(transfer) = cb_read.as_mut() else {
+            return;
+        };
+        while transfer.index < transfer.buf.len() && !uart.uartfr.is_set(FLAG::RXFE) {
+            let byte = uart.uartdr.read(DATA::RAW) as u8;
+            if self.take_discard() {
+                continue;
+            }
+            transfer.buf[transfer.index] = byte;
+            transfer.index += 1;
+        }
+        if transfer.index == transfer.buf.len() || (timeout && transfer.index > 0) {
+            let transfer = cb_read.take().unwrap();
+            drop(cb_read);
+            (transfer.on_done)(transfer.buf, transfer.index);
+        }
+    }
+
+    /// 异步接收数据，直到填满 `buf`。
+    ///
+    /// 收到带错误标志的字节，或中断中检测到接收错误时，返回对应的 `ReadError`。
+    /// 接收溢出例外：溢出前进入 FIFO 的数据仍然有效，先读出这些数据，
+    /// 接收 FIFO 读空后才返回 `ReadError::Overrun`；`buf` 先填满时溢出留给下一次读取报告。
+    #[cfg(feature = "async")]
+    pub fn read<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+    ) -> impl Future<Output = Result<usize, ReadError>> + 'a {
+        ReadFuture {
+            uart: self,
+            buf,
+            index: 0,
+            completed: None,
+        }
+    }
+
+    /// 异步接收数据，遇到接收错误不停止：每个带错误标志的字节调用一次 `on_err`，
+    /// 不写入 `buf`，其余字节照常接收。`buf` 填满，或收到数据后线路空闲 32 个位时间
+    /// （与硬件接收超时相同）时完成，返回写入 `buf` 的字节数。
+    ///
+    /// 只带 OE 标志的字节本身是有效的，调用 `on_err(ReadError::Overrun)` 后仍然写入 `buf`。
+    /// 奇偶校验错误先按 `ParityErrorPolicy` 处理，丢弃策略下不调用 `on_err`。
+    /// 中断中记录的错误与字节上的标志重复，这里清除而不再报告。收到第一个字节之前
+    /// 由接收中断唤醒，之后的空闲计时使用通用定时器，future 每次 poll 都会立即重新唤醒自己。
+    #[cfg(feature = "async")]
+    pub fn read_with_error_handler<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+        on_err: fn(ReadError),
+    ) -> impl Future<Output = usize> + 'a {
+        ErrorHandlerReadFuture {
+            uart: self,
+            buf,
+            index: 0,
+            on_err,
+            deadline: None,
+        }
+    }
+
+    /// 阻塞发送数据，等待 FIFO 空间超过自旋上限时返回 `TimedOut`，剩余数据不再发送。
+    pub fn write_blocking(&self, data: &[u8]) -> Result<(), TimedOut> {
+        if self.write_iter(data.iter().copied()) < data.len() {
+            return Err(TimedOut);
+        }
+        Ok(())
+    }
+
+    /// 阻塞发送迭代器产生的字节，FIFO 满时等待，返回发送的字节数。
+    ///
+    /// 适合按需生成数据、没有连续缓冲区的场景。等待 FIFO 空间超过自旋上限时提前返回。
+    pub fn write_iter<I: Iterator<Item = u8>>(&self, iter: I) -> usize {
+        let uart = unsafe { self.base.as_ref() };
+        let mut count = 0;
+        for byte in iter {
+            if !self.spin_until(|| !uart.uartfr.is_set(FLAG::TXFF)) {
+                break;
+            }
+            uart.uartdr.write(DATA::RAW.val(byte as u32));
+            count += 1;
+        }
+        self.note_tx(count);
+        count
+    }
+
+    /// 阻塞发送 7 位数据，由软件计算校验位放在每个字节的第 8 位。
+    ///
+    /// 用于 UART 工作在 8N1、对端却期望 7 位数据加校验位的场合。每个字节只取低 7 位，
+    /// `Parity::None` 时第 8 位为 0。
+    pub fn write_with_parity(&self, data: &[u8], parity: Parity) {
+        self.write_iter(data.iter().map(|&byte| {
+            let byte = byte & 0x7f;
+            // 7 位数据中 1 的个数为奇数时，偶校验位为 1
+            let odd_ones = byte.count_ones() % 2 == 1;
+            let bit = match parity {
+                Parity::None => false,
+                Parity::Even => odd_ones,
+                Parity::Odd => !odd_ones,
+            };
+            byte | (bit as u8) << 7
+        }));
+    }
+
+    /// 非阻塞发送，在 `TXFF` 未置位时把字节写入发送 FIFO，返回本次接受的字节数。
+    ///
+    /// 可能只接受 `data` 的一部分，FIFO 已满时返回 0，调用者负责重试剩余部分。
+    pub fn try_write(&self, data: &[u8]) -> usize {
+        let uart = unsafe { self.base.as_ref() };
+        let mut count = 0;
+        for &byte in data {
+            if uart.uartfr.is_set(FLAG::TXFF) {
+                break;
+            }
+            uart.uartdr.write(DATA::RAW.val(byte as u32));
+            count += 1;
+        }
+        self.note_tx(count);
+        count
+    }
+
+    /// 等待发送 FIFO 有空间（`TXFF` 清除），之后用 `try_write` 写入数据。
+    ///
+    /// FIFO 满时注册发送 waker，由发送中断唤醒，需要使能发送中断。与 `write` 共用
+    /// 同一个发送 waker，不要与进行中的 `write` 同时等待。
+    #[cfg(feature = "async")]
+    pub fn wait_writable(&self) -> impl Future<Output = ()> + '_ {
+        WritableFuture { uart: self }
+    }
+
+    /// 同步地把 `data` 尽可能多地写入发送 FIFO，返回本次写入的字节数。
+    ///
+    /// 直接以 `write_volatile` 写数据寄存器，FIFO 为空时一次写满 FIFO 深度，
+    /// 调用者循环调用直到写完。用于测量吞吐量等高级场景，是异步写开销的对照基线。
+    pub fn write_bulk(&self, data: &[u8]) -> usize {
+        let uart = unsafe { self.base.as_ref() };
+        // uartdr 位于寄存器块偏移 0 处
+        let dr = self.base.as_ptr() as *mut u32;
+        let mut count = 0;
+        while count < data.len() {
+            let end = if uart.uartfr.is_set(FLAG::TXFE) {
+                (count + self.fifo_depth()).min(data.len())
+            } else if !uart.uartfr.is_set(FLAG::TXFF) {
+                count + 1
+            } else {
+                break;
+            };
+            for &byte in &data[count..end] {
+                unsafe { dr.write_volatile(DATA::RAW.val(byte as u32).value) };
+            }
+            count = end;
+        }
+        // 保证返回前写操作已经发出
+        mbarrier::wmb();
+        count
+    }
+
+    /// 用 `write_bulk` 把 `pattern` 连续发送 `reps` 次，返回实测吞吐量（字节/秒）。
+    ///
+    /// `now_ns` 由调用者提供单调递增的纳秒时间，驱动本身不依赖定时器。计时在最后一个
+    /// 字节离开移位寄存器后结束，可以用来确认实际波特率，结果约为波特率除以每帧位数。
+    /// 用时为 0（时钟精度不足）时返回 0。
+    pub fn throughput_test(&self, pattern: &[u8], reps: usize, now_ns: impl Fn() -> u64) -> u64 {
+        let start = now_ns();
+        for _ in 0..reps {
+            let mut sent = 0;
+            while sent < pattern.len() {
+                let n = self.write_bulk(&pattern[sent..]);
+                if n == 0 {
+                    (self.spin_hook)();
+                }
+                sent += n;
+            }
+        }
+        let _ = self.flush();
+        let elapsed = now_ns().saturating_sub(start);
+        if elapsed == 0 {
+            return 0;
+        }
+        let bytes = (pattern.len() * reps) as u128;
+        (bytes * 1_000_000_000 / elapsed as u128) as u64
+    }
+
+    /// 在 `window_ns` 纳秒内反复采样 `BUSY`，返回发送器忙的采样比例（0.0 ~ 1.0）。
+    ///
+    /// 用于估计链路接近饱和的程度，是 `throughput_test` 之外的被动诊断，不发送任何数据。
+    /// 结果是采样估计，采样间隔由 `now_ns` 的开销和自旋钩子决定。
+    pub fn utilization(&self, window_ns: u64, now_ns: impl Fn() -> u64) -> f32 {
+        let uart = unsafe { self.base.as_ref() };
+        let start = now_ns();
+        let (mut busy, mut samples) = (0u64, 0u64);
+        loop {
+            samples += 1;
+            if uart.uartfr.is_set(FLAG::BUSY) {
+                busy += 1;
+            }
+            if now_ns().saturating_sub(start) >= window_ns {
+                break;
+            }
+            (self.spin_hook)();
+        }
+        busy as f32 / samples as f32
+    }
+
+    /// 等待发送完成，BUSY 超过自旋上限仍未清除时返回 `TimedOut`。
+    pub fn flush(&self) -> Result<(), TimedOut> {
+        if !self.wait_busy_clear() {
+            warn!("UART BUSY flag stuck, call recover() to reset the transmitter");
+            return Err(TimedOut);
+        }
+        Ok(())
+    }
+
+    /// 把 UART 交给另一个核之前调用：等待已写入的数据发完，屏蔽全部中断，
+    /// 再插入完整的内存屏障，保证本核之前的寄存器写入和驱动状态对新的核可见。
+    ///
+    /// 调用者还需要自行保证：
+    /// - 本核不再调用这个 `Uart` 的任何方法，也没有未完成的 future；
+    /// - 中断处理函数已经返回（例如用 GIC 关闭中断后等待在途的 `handle_interrupt` 结束），
+    ///   屏蔽中断只能阻止之后的中断；
+    /// - 通过带 Release/Acquire 语义的同步（锁、原子标志或核间中断）把 `Uart` 交给新的核，
+    ///   新的核在取得所有权后重新设置中断屏蔽和中断路由。
+    ///
+    /// 回调发送中尚未写入 FIFO 的数据依赖中断，调用前应等它完成。等待发送超过自旋上限时
+    /// 仍然屏蔽中断并插入屏障，返回 `TimedOut` 表示可能有数据没有发出。
+    pub fn transfer_ownership(&self) -> Result<(), TimedOut> {
+        let uart = unsafe { self.base.as_ref() };
+        let flushed = self.flush();
+        critical(|| uart.uartimsc.set(0));
+        // 驱动状态的原子变量和寄存器写入都在屏障之前完成
+        core::sync::atomic::fence(Ordering::SeqCst);
+        mbarrier::mb();
+        flushed
+    }
+
+    /// 发送路径是否完全空闲：发送 FIFO 为空、BUSY 清除，且没有进行中的回调发送。
+    ///
+    /// 供空闲钩子在进入 `wfi` 或关闭时钟前判断，返回 `false` 时应保持时钟运行，
+    /// 否则尚未发出的日志会被截断。只读取状态，不修改任何寄存器。
+    pub fn prepare_for_idle(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        let fr = uart.uartfr.extract();
+        fr.is_set(FLAG::TXFE) && !fr.is_set(FLAG::BUSY) && self.cb_write.lock().is_none()
+    }
+
+    /// 自旋等待发送 FIFO 清空且 BUSY 清除，最多 `max_spins` 次，返回是否发送完成。
+    ///
+    /// 用于 panic 处理等最受限的场合：不依赖中断和执行器，不调用 `set_spin_hook`
+    /// 设置的钩子（钩子可能是 `wfi`），硬件失效时也能在有限时间内返回。
+    ///
+    /// ```ignore
+    /// #[panic_handler]
+    /// fn panic(info: &core::panic::PanicInfo) -> ! {
+    ///     let mut uart = Uart::<16>::new(UART_BASE as *mut u8);
+    ///     let _ = writeln!(uart, "{info}");
+    ///     uart.busy_wait_tx_complete(1_000_000);
+    ///     loop {
+    ///         aarch64_cpu::asm::wfe();
+    ///     }
+    /// }
+    /// ```
+    pub fn busy_wait_tx_complete(&self, max_spins: u32) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        let done = || {
+            let fr = uart.uartfr.extract();
+            fr.is_set(FLAG::TXFE) && !fr.is_set(FLAG::BUSY)
+        };
+        let mut spins = 0;
+        while !done() {
+            if spins >= max_spins {
+                return false;
+            }
+            spins += 1;
+            core::hint::spin_loop();
+        }
+        true
+    }
+
+    // 自旋等待条件成立，超过自旋上限返回 false
+    pub(crate) fn spin_until(&self, mut done: impl FnMut() -> bool) -> bool {
+        let mut spins = 0;
+        while !done() {
+            if spins >= self.busy_spin_cap {
+                return false;
+            }
+            spins += 1;
+            (self.spin_hook)();
+        }
+        true
+    }
+
+    // 等待 BUSY 清除，超过自旋上限返回 false
+    fn wait_busy_clear(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        self.spin_until(|| !uart.uartfr.is_set(FLAG::BUSY))
+    }
+
+    /// `bits` 个位时间对应的纳秒数，由当前分频值和 `init`/`set_baud` 传入的时钟换算，
+    /// 向上取整。未初始化时钟时返回 0。
+    pub fn bits_to_ns(&self, bits: u32) -> u64 {
+        let clk = self.clk.load(Ordering::Relaxed) as u64;
+        if clk == 0 {
+            return 0;
+        }
+        let uart = unsafe { self.base.as_ref() };
+        // 位时间 = 16 * (ibrd + fbrd / 64) / clk = divisor / (4 * clk)
+        let divisor = (uart.uartibrd.get() as u64) * 64 + uart.uartfbrd.get() as u64;
+        (bits as u64 * divisor * 1_000_000_000).div_ceil(4 * clk)
+    }
+
+    /// 忙等 `bits` 个位时间，`now_ns` 是调用者提供的单调纳秒时钟。
+    ///
+    /// 与发送状态无关，从调用时刻开始计时。等待期间调用 `set_spin_hook` 设置的钩子。
+    pub fn wait_bits(&self, bits: u32, now_ns: impl Fn() -> u64) {
+        let duration = self.bits_to_ns(bits);
+        let start = now_ns();
+        while now_ns().saturating_sub(start) < duration {
+            (self.spin_hook)();
+        }
+    }
+
+    /// `wait_bits` 的异步版本，从调用时刻开始计时。
+    ///
+    /// future 在等待期间每次 poll 都会立即重新唤醒自己，实际时间受执行器调度影响，
+    /// 只保证不短于请求值。
+    #[cfg(feature = "async")]
+    pub fn wait_bits_async<F: Fn() -> u64>(&self, bits: u32, now_ns: F) -> WaitBitsFuture<F> {
+        WaitBitsFuture {
+            start: now_ns(),
+            duration: self.bits_to_ns(bits),
+            now_ns,
+        }
+    }
+
+    /// 等待发送完成后，让线路保持 mark 状态 `bits` 个位时间，用于产生帧间空闲间隔。
+    ///
+    /// 位时间由当前分频值和 `init`/`set_baud` 传入的时钟换算，用 ARM 通用定时器计时。
+    /// future 在等待期间每次 poll 都会立即重新唤醒自己，实际间隔受执行器调度影响，
+    /// 只保证不短于请求值。未初始化时钟时立即完成。
+    #[cfg(feature = "async")]
+    pub fn idle_gap(&self, bits: u32) -> impl Future<Output = ()> + '_ {
+        IdleGapFuture {
+            uart: self,
+            bits,
+            deadline: None,
+        }
+    }
+
+    // `bits` 个位时间对应的通用定时器计数
+    #[cfg(feature = "async")]
+    fn bit_ticks(&self, bits: u32) -> u64 {
+        let clk = self.clk.load(Ordering::Relaxed) as u64;
+        if clk == 0 {
+            return 0;
+        }
+        let uart = unsafe { self.base.as_ref() };
+        // 位时间 = 16 * (ibrd + fbrd / 64) / clk = divisor / (4 * clk)
+        let divisor = (uart.uartibrd.get() as u64) * 64 + uart.uartfbrd.get() as u64;
+        let freq = CNTFRQ_EL0.get();
+        (bits as u64 * divisor * freq).div_ceil(4 * clk)
+    }
+
+    /// 发完所有数据后关闭 UART。
+    ///
+    /// 顺序为：把回调发送中尚未进入 FIFO 的数据写入 FIFO，等待发送 FIFO 清空（`TXFE`），
+    /// 再等待最后一个字节移出移位寄存器（`BUSY` 清除），最后清零 `uartcr`。
+    /// 提前关闭会截断最后几个字节。每一步的等待都受自旋上限约束，
+    /// 任何一步超时仍会关闭 UART，并返回 `TimedOut` 表示可能有数据没有发出。
+    /// 同一个控制器常被多个 `Uart`（例如控制台）共用，因此 `Uart` 被丢弃时不会自动调用。
+    pub fn disable(&self) -> Result<(), TimedOut> {
+        let uart = unsafe { self.base.as_ref() };
+        let queued = self.spin_until(|| self.fill_cb_write());
+        let empty = self.spin_until(|| uart.uartfr.is_set(FLAG::TXFE));
+        let idle = self.wait_busy_clear();
+        // 发送完成后结束回调发送，`on_done` 在这里而不是中断中调用
+        self.finish_cb_write();
+        uart.uartcr.set(0);
+        if queued && empty && idle {
+            Ok(())
+        } else {
+            Err(TimedOut)
+        }
+    }
+
+    /// 检测 BUSY 是否卡死，卡死时关闭再重新使能 UART 以复位发送器。
+    ///
+    /// 复位会丢弃发送 FIFO 中尚未发出的数据。复位后 BUSY 仍然置位则返回错误。
+    pub fn recover(&self) -> Result<(), RecoverError> {
+        if self.wait_busy_clear() {
+            return Ok(());
+        }
+        warn!("UART BUSY flag stuck, resetting transmitter");
+        let uart = unsafe { self.base.as_ref() };
+        let cr = uart.uartcr.get();
+        uart.uartcr.set(0);
+        uart.uartcr.set(cr);
+        if self.wait_busy_clear() {
+            Ok(())
+        } else {
+            Err(RecoverError::StillBusy)
+        }
+    }
+
+    /// 发送 FIFO 是否还有空间（`TXFF` 未置位）。
+    ///
+    /// PL011 只提供空/满标志，没有 FIFO 水位寄存器，这是一个粗略判断。
+    pub fn tx_has_space(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        !uart.uartfr.is_set(FLAG::TXFF)
+    }
+
+    /// 发送 FIFO 中尚未发出的字节数。
+    ///
+    /// 没有水位寄存器时无法得到精确值：FIFO 为空返回 `Some(0)`，否则返回 `None`。
+    pub fn pending_tx_bytes(&self) -> Option<usize> {
+        let uart = unsafe { self.base.as_ref() };
+        if uart.uartfr.is_set(FLAG::TXFE) {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    // UART 是否已使能
+    pub fn is_enabled(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        uart.uartcr.is_set(CR::UARTEN)
+    }
+
+    /// 交换 TX/RX 引脚。
+    ///
+    /// PL011 及飞腾的 UART 控制器都没有引脚交换位，引脚复用需要在 SoC 的 pinmux 中配置。
+    /// 请求交换时总是返回 `Err(Unsupported)`，`false` 对应默认连线，直接返回成功。
+    pub fn set_pin_swap(&self, swap: bool) -> Result<(), Unsupported> {
+        if swap { Err(Unsupported) } else { Ok(()) }
+    }
+
+    // 开关内部回环，开启后发送的数据直接进入接收 FIFO
+    pub fn set_loopback(&self, enable: bool) {
+        let uart = unsafe { self.base.as_ref() };
+        if enable {
+            uart.uartcr.modify(CR::LBE::SET);
+        } else {
+            uart.uartcr.modify(CR::LBE::CLEAR);
+        }
+    }
+
+    /// 接收 FIFO 中是否有数据（`RXFE` 未置位），总是可用。
+    pub fn has_rx_data(&self) -> bool {
+        let uart = unsafe { self.base.as_ref() };
+        !uart.uartfr.is_set(FLAG::RXFE)
+    }
+
+    /// 接收 FIFO 中可以立即读取的字节数。
+    ///
+    /// PL011 没有水位寄存器，只有 FIFO 为空（`Some(0)`）或已满（`Some(DEPTH)`，
+    /// 字符模式下为 `Some(1)`）时
+    /// 能给出精确值，其余情况返回 `None`，此时请用 `has_rx_data` 判断。
+    pub fn rx_available(&self) -> Option<usize> {
+        let uart = unsafe { self.base.as_ref() };
+        if uart.uartfr.is_set(FLAG::RXFE) {
+            Some(0)
+        } else if uart.uartfr.is_set(FLAG::RXFF) {
+            Some(self.fifo_depth())
+        } else {
+            None
+        }
+    }
+
+    // 中断中检测到的 break 次数
+    pub fn break_count(&self) -> usize {
+        self.break_count.load(Ordering::Relaxed)
+    }
+
+    /// 设置 break 的最短长度（位时间），更短的 break 不计入 `break_count`，也不唤醒
+    /// `wait_for_break`，用于 LIN 从机过滤噪声引起的短 break。0 表示不过滤（默认）。
+    ///
+    /// PL011 只在线路保持低电平超过一帧时报告 BE，不给出持续时间。这里按一帧 10 位估计
+    /// BE 报告前的长度，再加上从 BE 中断到下一次接收或接收超时中断之间的时间，
+    /// 包含了 break 后的间隔和空闲，因此只是上限估计：能过滤 break 后紧跟数据的短 break，
+    /// 但 break 之后线路空闲时短 break 也可能被计入。`read` 报告的 `ReadError::Break`
+    /// 和 `peek_errors` 不受此设置影响。
+    #[cfg(feature = "async")]
+    pub fn set_break_threshold(&mut self, bits: u32) {
+        self.break_threshold = bits;
+    }
+
+    // 判断本次中断是否产生了一次计入的 break
+    #[cfg(not(feature = "async"))]
+    fn detect_break(&self, errors: u32, _irq: InterruptStatus) -> bool {
+        errors & (1 << ICR::BEIC.shift) != 0
+    }
+
+    // 判断本次中断是否产生了一次计入的 break
+    #[cfg(feature = "async")]
+    fn detect_break(&self, errors: u32, irq: InterruptStatus) -> bool {
+        let be = errors & (1 << ICR::BEIC.shift) != 0;
+        if self.break_threshold > BREAK_REPORT_BITS {
+            let now = CNTPCT_EL0.get();
+            let started = self.break_started.swap(0, Ordering::AcqRel);
+            if be {
+                // 检测时刻不能是 0，否则会被当作没有等待中的 break
+                self.break_started.store(now.max(1), Ordering::Release);
+                return false;
+            }
+            if started == 0 {
+                return false;
+            }
+            if !irq.rx && !irq.rx_timeout {
+                // 不是接收事件，继续等待
+                self.break_started.store(started, Ordering::Release);
+                return false;
+            }
+            let remaining = self.break_threshold - BREAK_REPORT_BITS;
+            return now - started >= self.bit_ticks(remaining);
+        }
+        be
+    }
+
+    /// 等待下一次收到 break，用于 LIN 等以 break 作为同步信号的协议。
+    ///
+    /// break 在 `handle_interrupt` 中检测，需要使能接收中断。
+    #[cfg(feature = "async")]
+    pub fn wait_for_break(&self) -> impl Future<Output = ()> + '_ {
+        BreakFuture {
+            uart: self,
+            start: self.break_count(),
+        }
+    }
+
+    /// 使能 CTS 调制解调器状态中断（CTSMIM），等待 CTS 下一次变化，完成时给出新的 CTS 状态
+    /// （`true` 表示对端允许发送）。
+    ///
+    /// 变化在 `handle_interrupt` 中检测。发送方可以在对端撤销 CTS 后等待它重新置位，
+    /// 而不必轮询 `uartfr`。
+    #[cfg(feature = "async")]
+    pub fn wait_cts_change(&self) -> impl Future<Output = bool> + '_ {
+        let uart = unsafe { self.base.as_ref() };
+        let start = self.cts_count.load(Ordering::Acquire);
+        critical(|| uart.uartimsc.modify(INTERRUPT::CTSMIM::SET));
+        CtsChangeFuture { uart: self, start }
+    }
+
+    /// 等待线路空闲：最后一个字节之后 `idle_bits` 个位时间内没有收到新数据。
+    ///
+    /// 以接收超时中断（RTIM）作为空闲信号，需要使能并在 `handle_interrupt` 中处理该中断。
+    /// 硬件的超时固定为 32 个位时间，且只在接收 FIFO 中留有数据时触发，
+    /// 因此调用者在等待期间不能把 FIFO 读空。`idle_bits` 超过 32 时，超时中断之后
+    /// 再用通用定时器等待剩余的位时间，这段时间内到达的数据不会重新开始计时。
+    #[cfg(feature = "async")]
+    pub fn wait_line_idle(&self, idle_bits: u32) -> impl Future<Output = ()> + '_ {
+        LineIdleFuture {
+            uart: self,
+            start: self.idle_count.load(Ordering::Acquire),
+            extra_bits: idle_bits.saturating_sub(32),
+            deadline: None,
+        }
+    }
+
+    /// 读出并丢弃接收数据，直到线路空闲 `idle_bits` 个位时间，返回丢弃的字节数。
+    ///
+    /// 用于出错后丢弃残留数据、重新同步。每收到一个字节就重新开始计时，计时使用
+    /// 通用定时器而不是接收超时中断，因为读空 FIFO 后超时中断不会触发。
+    /// 等待期间 future 每次 poll 都会立即重新唤醒自己。
+    #[cfg(feature = "async")]
+    pub fn drain_until_idle(&self, idle_bits: u32) -> impl Future<Output = usize> + '_ {
+        DrainIdleFuture {
+            uart: self,
+            idle_bits,
+            discarded: 0,
+            deadline: None,
+        }
+    }
+
+    // 开关 break 发送，开启期间 TX 持续输出低电平
+    pub fn set_break(&self, enable: bool) {
+        let uart = unsafe { self.base.as_ref() };
+        if enable {
+            uart.uartlcrh.modify(LCRH::BRK::SET);
+        } else {
+            uart.uartlcrh.modify(LCRH::BRK::CLEAR);
+        }
+    }
+
+    /// 依次取出接收 FIFO 中的数据，FIFO 为空时结束。
+    ///
+    /// 每次调用 `next` 都会从 FIFO 弹出一个字节，只在确实需要数据时才调用。
+    /// 这是原始读取：不丢弃 `set_warmup_bytes` 的预热字节和 `set_suppress_tx_echo` 的回显，
+    /// 也不影响它们的计数，适合丢弃残留数据；需要交付给上层的数据用 `try_read`。
+    pub fn rx_iter(&self) -> impl Iterator<Item = u8> + '_ {
+        let uart = unsafe { self.base.as_ref() };
+        core::iter::from_fn(move || {
+            if uart.uartfr.is_set(FLAG::RXFE) {
+                None
+            } else {
+                Some(uart.uartdr.read(DATA::RAW) as u8)
+            }
+        })
+    }
+
+    /// 非阻塞读取，把接收 FIFO 中的数据读入 `buf`，返回读取的字节数。
+    ///
+    /// 最多读取 `buf.len()` 个字节，FIFO 中剩余的数据留给下一次读取。
+    /// 与 `try_receive` 相同，跳过预热字节和回显，不检查错误标志。
+    pub fn try_read(&self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        // buf 放在 zip 前面，buf 写满后不会再从 FIFO 多弹出一个字节
+        for (slot, byte) in buf
+            .iter_mut()
+            .zip(core::iter::from_fn(|| self.try_receive()))
+        {
+            *slot = byte;
+            count += 1;
+        }
+        count
+    }
+
+    /// 读取 `uartris` 中的接收错误标志，不清除标志，也不从 FIFO 取出数据。
+    ///
+    /// 这些是整条线路的粘滞标志，表示自上次清除以来发生过的错误，不对应某个具体字节；
+    /// 逐字节的错误需要用 `receive_checked` 读取。`handle_interrupt` 会清除这些标志。
+    pub fn peek_errors(&self) -> ErrorFlags {
+        let uart = unsafe { self.base.as_ref() };
+        ErrorFlags::from_status(uart.uartris.get())
+    }
+
+    /// 非阻塞地读取一个字节并检查错误标志，接收 FIFO 为空时返回 `None`。
+    #[must_use]
+    pub fn receive_checked(&self) -> Option<Result<u8, ReadError>> {
+        let uart = unsafe { self.base.as_ref() };
+        loop {
+            if uart.uartfr.is_set(FLAG::RXFE) {
+                return None;
+            }
+            let data = uart.uartdr.extract();
+            if self.take_discard() {
+                continue;
+            }
+            return Some(match ReadError::from_data(data) {
+                Some(err) => Err(err),
+                None => Ok(data.read(DATA::RAW) as u8),
+            });
+        }
+    }
+
+    /// 非阻塞地读取一个字节，接收 FIFO 为空时返回 `None`，不检查错误标志。
+    pub fn try_receive(&self) -> Option<u8> {
+        let uart = unsafe { self.base.as_ref() };
+        loop {
+            if uart.uartfr.is_set(FLAG::RXFE) {
+                return None;
+            }
+            let byte = uart.uartdr.read(DATA::RAW) as u8;
+            if !self.take_discard() {
+                return Some(byte);
+            }
+        }
+    }
 
-unsafe impl Send for Uart {}
-unsafe impl Sync for Uart {}
+    /// 读取 `samples` 个字节，帧错误（含 break）占多数时返回 `true`，提示波特率配置不对。
+    ///
+    /// 每个字节的等待受自旋上限约束，没有数据到达时提前结束，只按已收到的字节判断，
+    /// 一个字节都没有收到时返回 `false`。
+    pub fn detect_baud_mismatch(&self, samples: usize) -> bool {
+        let mut received = 0;
+        let mut framing = 0;
+        while received < samples && self.spin_until(|| self.has_rx_data()) {
+            if let Some(Err(ReadError::Framing | ReadError::Break)) = self.receive_checked() {
+                framing += 1;
+            }
+            received += 1;
+        }
+        received > 0 && framing * 2 > received
+    }
 
-impl Uart {
-    pub const fn new(base: *mut u8) -> Self {
-        Self {
-            base: NonNull::new(base).unwrap().cast(),
-            waker: AtomicWaker::new(),
-            irq_conut: 0,
+    /// 在 `sample_bits` 个位时间的窗口内采样接收错误，判断 RX 线路的状态。
+    ///
+    /// 开始时清除帧错误、校验错误和 break 标志，窗口内读出并丢弃收到的所有数据。
+    /// 收到的只有 break 时返回 `StuckBreak`（线路持续为低时 PL011 只报告一次 break），
+    /// 出错的帧多于正常的帧时返回 `Floating`。波特率不匹配同样会产生大量帧错误，
+    /// 但通常混有能正常收到的字节，因此需要结合 `detect_baud_mismatch` 判断。
+    /// 上拉到空闲电平的断线与空闲线路无法区分，都返回 `Ok`。
+    /// `now_ns` 是调用者提供的单调纳秒时钟，UART 尚未初始化时只采样一次。
+    pub fn rx_line_health(&self, sample_bits: u32, now_ns: impl Fn() -> u64) -> LineHealth {
+        let uart = unsafe { self.base.as_ref() };
+        uart.uarticr
+            .write(ICR::FEIC::SET + ICR::PEIC::SET + ICR::BEIC::SET);
+        let window = self.bits_to_ns(sample_bits);
+        let (mut good, mut errors, mut breaks) = (0u32, 0u32, 0u32);
+        let start = now_ns();
+        loop {
+            match self.receive_checked() {
+                Some(Ok(_)) => good += 1,
+                Some(Err(ReadError::Break)) => breaks += 1,
+                Some(Err(_)) => errors += 1,
+                None => (self.spin_hook)(),
+            }
+            if now_ns().saturating_sub(start) >= window {
+                break;
+            }
+        }
+        // 没有读到数据时仍可能已经置起粘滞的错误标志
+        let flags = self.peek_errors();
+        if breaks == 0 && flags.break_detected {
+            breaks = 1;
+        }
+        if errors == 0 && (flags.framing || flags.parity) {
+            errors = 1;
+        }
+        if breaks > 0 && good == 0 && errors == 0 {
+            LineHealth::StuckBreak
+        } else if breaks + errors > good {
+            LineHealth::Floating
+        } else {
+            LineHealth::Ok
+        }
+    }
+
+    // 接收数据，接收 FIFO 为空时返回 0；需要区分时使用 `try_receive` 或异步的 `read`。
+    // 与 `try_receive` 相同，跳过预热字节和回显
+    pub fn receive(&self) -> u8 {
+        self.try_receive().unwrap_or_else(|| {
+            warn!("FIFO is empty, no data to receive");
+            0 // 或者返回一个错误值
+        })
+    }
+
+    /// 拆分为发送和接收两半，可以分别移动到不同的任务中。
+    ///
+    /// 两半通过 `Arc` 共享同一个 `Uart`，只访问各自方向的寄存器和 waker，
+    /// 中断处理通过 `UartRx::handle_interrupt` 同时服务两个方向。
+    pub fn split(self) -> (UartTx<DEPTH, W>, UartRx<DEPTH, W>) {
+        let uart = Arc::new(self);
+        (UartTx { uart: uart.clone() }, UartRx { uart })
+    }
+
+    // 进入 `handle_interrupt` 的次数
+    pub fn irq_count(&self) -> usize {
+        self.irq_count.load(Ordering::Relaxed)
+    }
+
+    // 中断中检测到的接收溢出次数
+    pub fn overrun_count(&self) -> usize {
+        self.overrun_count.load(Ordering::Relaxed)
+    }
+
+    /// 把中断次数、接收溢出次数、break 次数和发送背压次数清零，用于每个统计周期开始时。
+    ///
+    /// 清零期间屏蔽本 UART 的中断（见 `with_interrupts_disabled`），`handle_interrupt`
+    /// 不会在清零中途更新计数，几个计数器相对中断处理是一起清零的。调用前已经在执行的
+    /// 中断处理（例如另一个核上的）不受屏蔽影响，需要时由调用者先同步。
+    /// 异步写在任务上下文中更新的发送背压计数不受屏蔽保护，清零瞬间的一次事件可能丢失。
+    /// 正在等待 break 的 future 以计数变化判断 break，清零本身也会使其完成。
+    pub fn reset_counters(&self) {
+        self.with_interrupts_disabled(|| {
+            // 与各处的自增一样使用 Relaxed，计数只用于统计，不用于同步
+            self.irq_count.store(0, Ordering::Relaxed);
+            self.overrun_count.store(0, Ordering::Relaxed);
+            self.break_count.store(0, Ordering::Relaxed);
+            self.tx_backpressure.store(0, Ordering::Relaxed);
+        });
+    }
+
+    /// 汇总各个计数器和线路状态，用于状态行或 shell 命令一次输出驱动的运行状态。
+    ///
+    /// 线路状态只根据 `peek_errors` 的粘滞标志判断，不读取接收 FIFO：只有 break 标志时为
+    /// `StuckBreak`，有帧错误或校验错误时为 `Floating`。需要按时间窗口采样的准确判断时
+    /// 使用 `rx_line_health`。
+    pub fn health(&self) -> Health {
+        let flags = self.peek_errors();
+        let line = if flags.framing || flags.parity {
+            LineHealth::Floating
+        } else if flags.break_detected {
+            LineHealth::StuckBreak
+        } else {
+            LineHealth::Ok
+        };
+        Health {
+            irq_count: self.irq_count(),
+            overrun_count: self.overrun_count(),
+            break_count: self.break_count(),
+            tx_backpressure_events: self.tx_backpressure_events(),
+            line,
         }
     }
 
-    pub fn init(&self, clk_rate: u32, baud_rate: u32) {
+    // 读取中断次数并清零，用于按时间段统计中断频率
+    pub fn take_irq_count(&self) -> usize {
+        self.irq_count.swap(0, Ordering::Relaxed)
+    }
+
+    /// 清除全部挂起的中断，包括接收错误和调制解调器状态中断。
+    ///
+    /// 用于错误恢复后或使能中断之前有意丢弃所有挂起状态。`handle_interrupt` 只清除
+    /// 自己处理过的中断，不会用到本方法；在中断处理之外调用会丢掉尚未处理的中断，
+    /// 等待它们的 future 不会被唤醒。
+    pub fn clear_all_interrupts(&self) {
         let uart = unsafe { self.base.as_ref() };
-        // 关闭 UART
-        uart.uartcr.set(0);
-        // 设置波特率
-        let integer_part = clk_rate / (16 * baud_rate);
-        let fraction_part = ((clk_rate % (16 * baud_rate)) * 64 / (16 * baud_rate)) as u8;
-        info!(
-            "integer_part is {}, fraction_part is {}",
-            integer_part, fraction_part
+        uart.uarticr.write(
+            ICR::RIMIC::SET
+                + ICR::CTSMIC::SET
+                + ICR::DCDMIC::SET
+                + ICR::DSRMIC::SET
+                + ICR::RXIC::SET
+                + ICR::TXIC::SET
+                + ICR::RTIC::SET
+                + ICR::FEIC::SET
+                + ICR::PEIC::SET
+                + ICR::BEIC::SET
+                + ICR::OEIC::SET,
         );
-        uart.uartibrd.set(integer_part);
-        uart.uartfbrd.set(fraction_part as u32);
-        // 使能fifo
-        uart.uartifls.set(0x20);
-        // 启用中断
-        uart.uartimsc.set(1 << 4 | 1 << 5);
-        // 配置 UART
-        info!("configuring UART");
-        uart.uartlcrh.set(0x70); // 8位数据, 无奇偶校验, 1位停止位, FIFOs使能
-        uart.uartcr.set(0x301); // 使能UART, 使能接收和发送
     }
 
-    // 发送数据
+    /// 注册发送中断（发送 FIFO 降到触发水位以下）时调用的回调，`None` 取消注册。
+    ///
+    /// 用于推送式的生产者在 FIFO 有空间后及时补充数据，是异步写之外的另一种方式。
+    /// 回调在 `handle_interrupt` 中、即中断上下文里调用，必须尽快返回，不能阻塞，
+    /// 可以调用 `try_write` 等非阻塞方法补充数据。
+    pub fn on_tx_ready(&self, cb: Option<fn()>) {
+        *self.tx_ready.lock() = cb;
+    }
+
+    /// 自上次调用以来 `handle_interrupt` 是否观察到接收溢出，调用后清除。
+    ///
+    /// 与读操作报告的 `ReadError::Overrun` 相互独立，适合在主循环中做轻量的健康检查。
+    pub fn rx_overran_since(&self) -> bool {
+        self.rx_overran.swap(false, Ordering::AcqRel)
+    }
+
+    /// 异步写因发送 FIFO 满而等待（返回 `Pending`）的次数。
+    ///
+    /// 相对发送字节数的比例越高，说明波特率或对端跟不上生产者，可用于调整发送节奏。
+    pub fn tx_backpressure_events(&self) -> usize {
+        self.tx_backpressure.load(Ordering::Relaxed)
+    }
+
+    /// 中断处理函数。
+    ///
+    /// 先读取 `uartris` 快照并只清除快照中的中断位，再检查 FIFO 状态和唤醒 waker。
+    /// 如果先检查 FIFO 再清除全部中断，检查之后、清除之前到达的数据产生的中断会被
+    /// 一起清掉，等待它的 future 不会再被唤醒。按现在的顺序，清除之前到达的数据
+    /// 一定能在之后的检查中被看到，清除之后到达的数据会重新触发中断。
+    pub fn handle_interrupt(&self) {
+        self.irq_count.fetch_add(1, Ordering::Relaxed);
+        self.pump_cb_write();
+        let uart = unsafe { self.base.as_ref() };
+        let status = uart.uartris.get();
+        // 接收和发送中断可能同时挂起，两个方向都要在这一次处理中唤醒
+        let pending = uart.uartmis.get();
+        let irq = decode_mis(pending);
+        // 错误从 uartris 中取，屏蔽了错误中断时也要记录
+        let errors = status & RX_ERROR_BITS;
+        if errors != 0 {
+            self.rx_errors.fetch_or(errors, Ordering::AcqRel);
+        }
+        if decode_mis(status).overrun {
+            self.rx_overran.store(true, Ordering::Release);
+            self.overrun_count.fetch_add(1, Ordering::Relaxed);
+        }
+        let break_detected = self.detect_break(errors, irq);
+        if break_detected {
+            self.break_count.fetch_add(1, Ordering::Relaxed);
+        }
+        if irq.cts {
+            self.cts_count.fetch_add(1, Ordering::AcqRel);
+        }
+        if irq.rx_timeout {
+            self.idle_count.fetch_add(1, Ordering::AcqRel);
+        }
+        // uartris 与 uarticr 的位一一对应
+        uart.uarticr.set(status);
+        // 寄存器访问都是 volatile 的，彼此不会被重排；这里阻止编译器把错误记录
+        // 移到清除中断之后，或把 waker 的唤醒移到清除中断之前
+        compiler_fence(Ordering::SeqCst);
+        self.pump_cb_read(irq.rx_timeout);
+        if irq.rx_timeout {
+            self.idle_waker.wake();
+        }
+        if irq.cts {
+            self.cts_waker.wake();
+        }
+        if errors != 0 {
+            self.rx_waker.wake();
+        }
+        if break_detected {
+            self.break_waker.wake();
+        }
+        if irq.rx || irq.rx_timeout || !uart.uartfr.is_set(FLAG::RXFE) {
+            self.rx_waker.wake();
+        }
+        if irq.tx {
+            // 先取出回调再调用，调用期间不持有锁
+            let tx_ready = *self.tx_ready.lock();
+            if let Some(cb) = tx_ready {
+                cb();
+            }
+            self.waker.wake();
+        }
+    }
+}
+
+// 阻塞输出，可以配合 `write!` 使用
+impl<const DEPTH: usize, W: WakeSource> core::fmt::Write for Uart<DEPTH, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_blocking(s.as_bytes())
+            .map_err(|_| core::fmt::Error)
+    }
+}
+
+/// 通过共享引用阻塞输出的格式化适配器，例如 `core::fmt::write(&mut UartFmt(&uart), args)`。
+///
+/// 适用于中断处理或锁内只能拿到 `&Uart` 的场合，语义同 `Uart::write_blocking`。
+/// 多个上下文同时输出时字节可能交错。
+pub struct UartFmt<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource>(
+    pub &'a Uart<DEPTH, W>,
+);
+
+impl<const DEPTH: usize, W: WakeSource> core::fmt::Write for UartFmt<'_, DEPTH, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0
+            .write_blocking(s.as_bytes())
+            .map_err(|_| core::fmt::Error)
+    }
+}
+
+/// `Uart::split` 拆分出的发送半部
+pub struct UartTx<const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: Arc<Uart<DEPTH, W>>,
+}
+
+impl<const DEPTH: usize, W: WakeSource> UartTx<DEPTH, W> {
+    /// 异步发送数据，语义同 `Uart::write`。
+    #[cfg(feature = "async")]
     pub fn write<'a>(&'a mut self, data: &'a [u8]) -> impl Future<Output = usize> + 'a {
         WriteFuture {
-            uart: self,
+            uart: &self.uart,
             data,
             index: 0,
+            cooperative: false,
+            completed: false,
         }
     }
 
-    // 接收数据
-    pub fn receive(&self) -> u8 {
-        let uart = unsafe { self.base.as_ref() };
-        if uart.uartfr.get() & (1 << 5) != 0 {
-            warn!("FIFO is empty, no data to receive");
-            return 0; // 或者返回一个错误值
+    // 阻塞发送数据
+    pub fn write_blocking(&self, data: &[u8]) -> Result<(), TimedOut> {
+        self.uart.write_blocking(data)
+    }
+
+    /// 与接收半部重新合并为 `Uart`，用于重新配置端口。
+    ///
+    /// 两半必须来自同一次 `Uart::split`，否则原样返回两半。
+    pub fn reunite(self, rx: UartRx<DEPTH, W>) -> Result<Uart<DEPTH, W>, ReuniteError<DEPTH, W>> {
+        if !Arc::ptr_eq(&self.uart, &rx.uart) {
+            return Err(ReuniteError { tx: self, rx });
         }
-        uart.uartdr.get() as u8
+        drop(rx);
+        // 两半是仅有的两个引用，丢弃 rx 后这里一定能取回
+        Ok(Arc::into_inner(self.uart).unwrap())
     }
 
-    pub fn handle_interrupt(&mut self) {
-        self.irq_conut += 1;
-        unsafe {
-            if self.base.as_ref().uartfr.is_set(FLAG::RXFE) {
-                self.waker.wake();
-            }
-            self.base.as_ref().uarticr.set(u32::MAX);
+    // 等待发送完成
+    pub fn flush(&self) -> Result<(), TimedOut> {
+        self.uart.flush()
+    }
+}
+
+/// `Uart::split` 拆分出的接收半部
+pub struct UartRx<const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: Arc<Uart<DEPTH, W>>,
+}
+
+impl<const DEPTH: usize, W: WakeSource> UartRx<DEPTH, W> {
+    /// 异步接收数据直到填满 `buf`，语义同 `Uart::read`。
+    #[cfg(feature = "async")]
+    pub fn read<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+    ) -> impl Future<Output = Result<usize, ReadError>> + 'a {
+        ReadFuture {
+            uart: &self.uart,
+            buf,
+            index: 0,
+            completed: None,
         }
     }
+
+    /// 非阻塞地读取一个字节，语义同 `Uart::try_receive`。
+    pub fn try_receive(&self) -> Option<u8> {
+        self.uart.try_receive()
+    }
+
+    /// 中断处理，同时唤醒两个方向上等待的 future，语义同 `Uart::handle_interrupt`。
+    pub fn handle_interrupt(&self) {
+        self.uart.handle_interrupt();
+    }
 }
 
-pub struct WriteFuture<'a> {
-    uart: &'a Uart,
-    data: &'a [u8],
-    index: usize,
+/// `UartTx::reunite` 的错误：两半不是来自同一个 `Uart`，携带原来的两半
+pub struct ReuniteError<const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    pub tx: UartTx<DEPTH, W>,
+    pub rx: UartRx<DEPTH, W>,
+}
+
+impl<const DEPTH: usize, W: WakeSource> core::fmt::Debug for ReuniteError<DEPTH, W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ReuniteError")
+            .field("tx", &self.tx.uart.base)
+            .field("rx", &self.rx.uart.base)
+            .finish()
+    }
 }
 
-impl Future for WriteFuture<'_> {
+/// `Uart::save_state` 保存的全部可写寄存器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedState {
+    ibrd: u32,
+    fbrd: u32,
+    lcrh: u32,
+    cr: u32,
+    ifls: u32,
+    imsc: u32,
+    dmacr: u32,
+}
+
+/// `Uart::quiesce_for_config` 返回的配置 guard，可以解引用为寄存器块。
+pub struct ConfigGuard<'a> {
+    regs: &'a UartRegs,
+    cr: u32,
+    imsc: u32,
+}
+
+impl Deref for ConfigGuard<'_> {
+    type Target = UartRegs;
+
+    fn deref(&self) -> &Self::Target {
+        self.regs
+    }
+}
+
+impl Drop for ConfigGuard<'_> {
+    fn drop(&mut self) {
+        // 与 quiesce_for_config 保存时一样在临界区内恢复，不与中断中的修改交错
+        critical(|| self.regs.uartimsc.set(self.imsc));
+        self.regs.uartcr.set(self.cr);
+    }
+}
+
+/// 可以重复使用的发送对象，见 `Uart::transfer`
+#[cfg(feature = "async")]
+pub struct Transfer<'u, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'u mut Uart<DEPTH, W>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Transfer<'_, DEPTH, W> {
+    /// 发送 `data`，语义同 `Uart::write`。
+    ///
+    /// 返回的 future 被提前丢弃时，已写入 FIFO 的字节仍会发出，其余字节不再发送，
+    /// `Transfer` 可以继续用于下一次发送。
+    pub fn send<'a>(&'a mut self, data: &'a [u8]) -> impl Future<Output = usize> + 'a {
+        self.uart.write(data)
+    }
+}
+
+/// 按字符边界把字符串切分为不超过 `max_bytes` 字节的块。
+///
+/// 单个字符的编码长度超过 `max_bytes` 时，该字符单独成块。
+pub fn str_chunks(s: &str, max_bytes: usize) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    core::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let mut end = max_bytes.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = first.len_utf8();
+        }
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct WriteFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    pub(crate) uart: &'a Uart<DEPTH, W>,
+    pub(crate) data: &'a [u8],
+    pub(crate) index: usize,
+    // 每填满一次 FIFO 后让出执行器
+    pub(crate) cooperative: bool,
+    // 已经返回过 Ready，再次 poll 属于驱动 future 的错误
+    pub(crate) completed: bool,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for WriteFuture<'_, DEPTH, W> {
     type Output = usize;
 
-    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
+        // 完成后再次 poll：debug 构建下报错以发现重复驱动，release 构建下返回同样的结果
+        debug_assert!(!this.completed, "WriteFuture polled after completion");
+        if this.completed {
+            return Poll::Ready(this.index);
+        }
         unsafe {
             loop {
+                // 空切片在这里直接完成，不访问硬件也不注册 waker
                 if this.index >= this.data.len() {
+                    this.completed = true;
                     return Poll::Ready(this.index);
                 }
 
-                if this.uart.base.as_ref().uartfr.get() & (1 << 5) != 0 {
-                    this.uart.waker.register(_cx.waker());
-                    return Poll::Pending;
+                // FIFO 为空时可以一次写入整个 FIFO 深度的数据
+                if this.uart.base.as_ref().uartfr.is_set(FLAG::TXFE) {
+                    let end = (this.index + this.uart.fifo_depth()).min(this.data.len());
+                    for &data in &this.data[this.index..end] {
+                        this.uart
+                            .base
+                            .as_ref()
+                            .uartdr
+                            .write(DATA::RAW.val(data as u32));
+                    }
+                    this.uart.note_tx(end - this.index);
+                    this.index = end;
+                    if this.cooperative && this.index < this.data.len() {
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                    continue;
+                }
+
+                // 只在 FIFO 满时等待中断。FIFO 从满开始排空一定会经过触发水位，
+                // 因此无论水位设多高都会收到发送中断；FIFO 未满时直接写入，
+                // 不足触发水位的小块数据不会等待一个不会到来的中断
+                if this.uart.base.as_ref().uartfr.is_set(FLAG::TXFF) {
+                    // 先注册再重新检查：检查 TXFF 与注册之间触发的中断唤醒不到本 future，
+                    // 只有注册后 FIFO 仍然是满的才能返回 Pending
+                    this.uart.waker.register(cx.waker());
+                    if this.uart.base.as_ref().uartfr.is_set(FLAG::TXFF) {
+                        this.uart.tx_backpressure.fetch_add(1, Ordering::Relaxed);
+                        return Poll::Pending;
+                    }
+                    continue;
                 }
 
                 let data = this.data[this.index];
-                this.uart.base.as_ref().uartdr.set(data as u32);
+                this.uart
+                    .base
+                    .as_ref()
+                    .uartdr
+                    .write(DATA::RAW.val(data as u32));
+                this.uart.note_tx(1);
                 this.index += 1;
             }
         }
     }
 }
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct WritableFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for WritableFuture<'_, DEPTH, W> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let uart = unsafe { self.uart.base.as_ref() };
+        if !uart.uartfr.is_set(FLAG::TXFF) {
+            return Poll::Ready(());
+        }
+        // 与 WriteFuture 相同，注册后重新检查，避免丢失两者之间触发的中断
+        self.uart.waker.register(cx.waker());
+        if uart.uartfr.is_set(FLAG::TXFF) {
+            return Poll::Pending;
+        }
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct CtsWriteFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    inner: WriteFuture<'a, DEPTH, W>,
+    timeout_bits: u32,
+    // CTS 撤销后开始计时的截止时刻
+    deadline: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for CtsWriteFuture<'_, DEPTH, W> {
+    type Output = Result<usize, WriteError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Poll::Ready(written) = Pin::new(&mut this.inner).poll(cx) {
+            return Poll::Ready(Ok(written));
+        }
+        let uart = unsafe { this.inner.uart.base.as_ref() };
+        // FR 的 CTS 位是 nUARTCTS 的反相，置位表示对端允许发送
+        if !uart.uartcr.is_set(CR::CTSEN) || uart.uartfr.is_set(FLAG::CTS) {
+            this.deadline = None;
+            return Poll::Pending;
+        }
+        let now = CNTPCT_EL0.get();
+        let deadline = *this
+            .deadline
+            .get_or_insert_with(|| now + this.inner.uart.bit_ticks(this.timeout_bits));
+        if now >= deadline {
+            return Poll::Ready(Err(WriteError::FlowControlTimeout {
+                written: this.inner.index,
+            }));
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct CancellableWriteFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    inner: WriteFuture<'a, DEPTH, W>,
+    cancel: &'a AtomicBool,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for CancellableWriteFuture<'_, DEPTH, W> {
+    type Output = usize;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.cancel.load(Ordering::Acquire) {
+            return Poll::Ready(this.inner.index);
+        }
+        Pin::new(&mut this.inner).poll(cx)
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct ReadFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    pub(crate) uart: &'a Uart<DEPTH, W>,
+    pub(crate) buf: &'a mut [u8],
+    pub(crate) index: usize,
+    // 已经返回的结果，再次 poll 属于驱动 future 的错误
+    pub(crate) completed: Option<Result<usize, ReadError>>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> ReadFuture<'_, DEPTH, W> {
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize, ReadError>> {
+        let uart = unsafe { self.uart.base.as_ref() };
+        loop {
+            // 空缓冲区在这里直接完成，不访问硬件、不注册 waker，也不消耗中断中记录的错误
+            if self.index >= self.buf.len() {
+                return Poll::Ready(Ok(self.index));
+            }
+
+            let mut errors = self.uart.rx_errors.swap(0, Ordering::AcqRel);
+            if !uart.uartfr.is_set(FLAG::RXFE) {
+                // 出错的字节还在 FIFO 中，读到它时 uartdr 的标志会报告同一个错误，
+                // 这里丢弃中断记录的 BE/FE，避免一次错误报告两次
+                errors &= !RX_BYTE_ERROR_BITS;
+            }
+            if let Some(err) = ReadError::from_status(errors) {
+                if err == ReadError::Overrun && !uart.uartfr.is_set(FLAG::RXFE) {
+                    // 溢出时 FIFO 中的数据仍然有效，丢失的是之后到达的数据。
+                    // 先读出 FIFO 中的数据，FIFO 读空后再报告溢出
+                    self.uart.rx_errors.fetch_or(errors, Ordering::AcqRel);
+                } else {
+                    if err == ReadError::Overrun {
+                        self.uart.recover_overrun();
+                    }
+                    return Poll::Ready(Err(err));
+                }
+            }
+
+            if uart.uartfr.is_set(FLAG::RXFE) {
+                self.uart.unpace_rx();
+                // 与 WriteFuture 相同，注册后重新检查数据和中断中记录的错误
+                self.uart.rx_waker.register(cx.waker());
+                if uart.uartfr.is_set(FLAG::RXFE)
+                    && self.uart.rx_errors.load(Ordering::Acquire) == 0
+                {
+                    return Poll::Pending;
+                }
+                continue;
+            }
+
+            let data = uart.uartdr.extract();
+            if self.uart.take_discard() {
+                continue;
+            }
+            match ReadError::from_data(data) {
+                Some(ReadError::Parity(_))
+                    if self.uart.parity_policy == ParityErrorPolicy::Discard =>
+                {
+                    self.uart.parity_discarded.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                Some(ReadError::Overrun)
+                    if !data.is_set(DATA::BE)
+                        && !data.is_set(DATA::PE)
+                        && !data.is_set(DATA::FE) =>
+                {
+                    // 带 OE 的字节本身是有效的，收下它，按 uartris 中的溢出同样推迟到 FIFO 读空后报告
+                    self.uart
+                        .rx_errors
+                        .fetch_or(1 << ICR::OEIC.shift, Ordering::AcqRel);
+                }
+                Some(ReadError::Overrun) => {
+                    self.uart.recover_overrun();
+                    return Poll::Ready(Err(ReadError::Overrun));
+                }
+                Some(err) => return Poll::Ready(Err(err)),
+                None => {}
+            }
+            self.buf[self.index] = data.read(DATA::RAW) as u8;
+            self.index += 1;
+            self.uart.pace_rx();
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for ReadFuture<'_, DEPTH, W> {
+    type Output = Result<usize, ReadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        // 与 WriteFuture 相同，完成后再次 poll 在 debug 构建下报错，release 构建下返回同样的结果
+        debug_assert!(
+            this.completed.is_none(),
+            "ReadFuture polled after completion"
+        );
+        if let Some(result) = this.completed {
+            return Poll::Ready(result);
+        }
+        let result = core::task::ready!(this.poll_read(cx));
+        this.completed = Some(result);
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct ErrorHandlerReadFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+    buf: &'a mut [u8],
+    index: usize,
+    on_err: fn(ReadError),
+    // 最后一次收到数据后的空闲截止时刻，收到第一个字节之前为 None
+    deadline: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for ErrorHandlerReadFuture<'_, DEPTH, W> {
+    type Output = usize;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let uart = unsafe { this.uart.base.as_ref() };
+        this.uart.rx_errors.store(0, Ordering::Release);
+        while this.index < this.buf.len() && !uart.uartfr.is_set(FLAG::RXFE) {
+            let data = uart.uartdr.extract();
+            this.deadline = Some(CNTPCT_EL0.get() + this.uart.bit_ticks(32));
+            if this.uart.take_discard() {
+                continue;
+            }
+            match ReadError::from_data(data) {
+                Some(ReadError::Parity(_))
+                    if this.uart.parity_policy == ParityErrorPolicy::Discard =>
+                {
+                    this.uart.parity_discarded.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                Some(ReadError::Overrun)
+                    if !data.is_set(DATA::BE)
+                        && !data.is_set(DATA::PE)
+                        && !data.is_set(DATA::FE) =>
+                {
+                    (this.on_err)(ReadError::Overrun);
+                }
+                Some(err) => {
+                    (this.on_err)(err);
+                    continue;
+                }
+                None => {}
+            }
+            this.buf[this.index] = data.read(DATA::RAW) as u8;
+            this.index += 1;
+            this.uart.pace_rx();
+        }
+        if this.index >= this.buf.len() {
+            return Poll::Ready(this.index);
+        }
+        this.uart.unpace_rx();
+        match this.deadline {
+            Some(deadline) if CNTPCT_EL0.get() >= deadline => Poll::Ready(this.index),
+            Some(_) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            None => {
+                // 与 ReadFuture 相同，注册后重新检查，避免丢失两者之间到达的数据
+                this.uart.rx_waker.register(cx.waker());
+                if !uart.uartfr.is_set(FLAG::RXFE) {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct DrainFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for DrainFuture<'_, DEPTH, W> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let uart = unsafe { self.uart.base.as_ref() };
+        // FIFO 清空后 BUSY 才会在最后一个字节发完时清除
+        if uart.uartfr.is_set(FLAG::TXFE) && !uart.uartfr.is_set(FLAG::BUSY) {
+            return Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct WaitBitsFuture<F> {
+    now_ns: F,
+    start: u64,
+    duration: u64,
+}
+
+#[cfg(feature = "async")]
+impl<F: Fn() -> u64> Future for WaitBitsFuture<F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if (self.now_ns)().saturating_sub(self.start) >= self.duration {
+            return Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct IdleGapFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+    bits: u32,
+    // 发送完成后确定的结束时刻（通用定时器计数）
+    deadline: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for IdleGapFuture<'_, DEPTH, W> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let uart = unsafe { self.uart.base.as_ref() };
+                if !uart.uartfr.is_set(FLAG::TXFE) || uart.uartfr.is_set(FLAG::BUSY) {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                let deadline = CNTPCT_EL0.get() + self.uart.bit_ticks(self.bits);
+                self.deadline = Some(deadline);
+                deadline
+            }
+        };
+        if CNTPCT_EL0.get() >= deadline {
+            return Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct LineIdleFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+    start: usize,
+    // 超过硬件 32 位超时的部分
+    extra_bits: u32,
+    deadline: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for LineIdleFuture<'_, DEPTH, W> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let idle = |this: &Self| this.uart.idle_count.load(Ordering::Acquire) != this.start;
+                if !idle(&self) {
+                    self.uart.idle_waker.register(cx.waker());
+                    if !idle(&self) {
+                        return Poll::Pending;
+                    }
+                }
+                if self.extra_bits == 0 {
+                    return Poll::Ready(());
+                }
+                let deadline = CNTPCT_EL0.get() + self.uart.bit_ticks(self.extra_bits);
+                self.deadline = Some(deadline);
+                deadline
+            }
+        };
+        if CNTPCT_EL0.get() >= deadline {
+            return Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct DrainIdleFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+    idle_bits: u32,
+    discarded: usize,
+    // 最后一次收到数据后的空闲截止时刻
+    deadline: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for DrainIdleFuture<'_, DEPTH, W> {
+    type Output = usize;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let drained = self.uart.rx_iter().count();
+        self.discarded += drained;
+        let now = CNTPCT_EL0.get();
+        if drained > 0 || self.deadline.is_none() {
+            self.deadline = Some(now + self.uart.bit_ticks(self.idle_bits));
+        }
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            return Poll::Ready(self.discarded);
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct CtsChangeFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+    start: usize,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for CtsChangeFuture<'_, DEPTH, W> {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let changed = |this: &Self| this.uart.cts_count.load(Ordering::Acquire) != this.start;
+        if !changed(&self) {
+            self.uart.cts_waker.register(cx.waker());
+            if !changed(&self) {
+                return Poll::Pending;
+            }
+        }
+        let uart = unsafe { self.uart.base.as_ref() };
+        Poll::Ready(uart.uartfr.is_set(FLAG::CTS))
+    }
+}
+
+#[cfg(feature = "async")]
+#[must_use = "future 不被 `.await` 或轮询时什么也不做"]
+pub struct BreakFuture<'a, const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: &'a Uart<DEPTH, W>,
+    start: usize,
+}
+
+#[cfg(feature = "async")]
+impl<const DEPTH: usize, W: WakeSource> Future for BreakFuture<'_, DEPTH, W> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.uart.break_count() != self.start {
+            return Poll::Ready(());
+        }
+        self.uart.break_waker.register(cx.waker());
+        if self.uart.break_count() != self.start {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
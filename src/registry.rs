@@ -0,0 +1,93 @@
+//! 多个 PL011 实例时按中断号分发中断。
+
+use kspin::SpinNoIrq;
+
+use crate::{
+    pl011::Uart,
+    wake::{DefaultWakeSource, WakeSource},
+};
+
+// 中断号和登记在其上的实例
+type Entry<const DEPTH: usize, W> = (u32, &'static Uart<DEPTH, W>);
+
+/// 中断号到 `Uart` 的注册表，最多容纳 `N` 个实例。
+///
+/// ```ignore
+/// static UART0: Uart = Uart::new(UART0_BASE as *mut u8);
+/// static UART1: Uart = Uart::new(UART1_BASE as *mut u8);
+/// static UARTS: UartRegistry<2> = UartRegistry::new();
+///
+/// UARTS.register(UART0_IRQ, &UART0);
+/// UARTS.register(UART1_IRQ, &UART1);
+///
+/// // 中断处理函数中
+/// UARTS.dispatch(irq);
+/// ```
+pub struct UartRegistry<
+    const N: usize,
+    const DEPTH: usize = 16,
+    W: WakeSource + 'static = DefaultWakeSource,
+> {
+    entries: SpinNoIrq<[Option<Entry<DEPTH, W>>; N]>,
+}
+
+impl<const N: usize, const DEPTH: usize, W: WakeSource> Default for UartRegistry<N, DEPTH, W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const DEPTH: usize, W: WakeSource> UartRegistry<N, DEPTH, W> {
+    pub const fn new() -> Self {
+        Self {
+            entries: SpinNoIrq::new([None; N]),
+        }
+    }
+
+    /// 把 `uart` 登记到中断号 `irq`。
+    ///
+    /// 中断号已经登记过时替换原来的实例；注册表已满时返回 `false`。
+    pub fn register(&self, irq: u32, uart: &'static Uart<DEPTH, W>) -> bool {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries.iter_mut().flatten().find(|(id, _)| *id == irq) {
+            entry.1 = uart;
+            return true;
+        }
+        match entries.iter_mut().find(|entry| entry.is_none()) {
+            Some(slot) => {
+                *slot = Some((irq, uart));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 取消中断号 `irq` 的登记，返回原来登记的实例。
+    pub fn unregister(&self, irq: u32) -> Option<&'static Uart<DEPTH, W>> {
+        let mut entries = self.entries.lock();
+        let slot = entries
+            .iter_mut()
+            .find(|entry| matches!(entry, Some((id, _)) if *id == irq))?;
+        slot.take().map(|(_, uart)| uart)
+    }
+
+    /// 调用登记在 `irq` 上的实例的 `handle_interrupt`，没有登记时返回 `false`。
+    ///
+    /// 查找完成后才调用中断处理，处理过程中不持有注册表的锁。
+    pub fn dispatch(&self, irq: u32) -> bool {
+        let uart = self
+            .entries
+            .lock()
+            .iter()
+            .flatten()
+            .find(|(id, _)| *id == irq)
+            .map(|(_, uart)| *uart);
+        match uart {
+            Some(uart) => {
+                uart.handle_interrupt();
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! PL011 寄存器定义，可以独立于 `Uart` 驱动复用。
+
+use tock_registers::{
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+// 保留区域为私有字段，不对外暴露，驱动也从不访问：
+// 0x008~0x014 为保留；0x01c 为保留，0x020 是 IrDA 低功耗计数寄存器 UARTILPR，
+// 驱动不使用 IrDA，与 0x01c 一起按保留处理；0x04c~0xfdc 为保留和测试寄存器。
+// 读取这些偏移在部分集成中可能有副作用或触发总线错误，诊断代码也不能逐字遍历寄存器块。
+register_structs! {
+    pub UartRegs {
+        (0x000 => pub uartdr: ReadWrite<u32, DATA::Register>),
+        (0x004 => pub uartecr: ReadOnly<u32>),
+        (0x008 => _reserved0),
+        (0x018 => pub uartfr: ReadOnly<u32, FLAG::Register>),
+        (0x01c => _reserved1),
+        (0x024 => pub uartibrd: ReadWrite<u32>),
+        (0x028 => pub uartfbrd: ReadWrite<u32>),
+        (0x02c => pub uartlcrh: ReadWrite<u32, LCRH::Register>),
+        (0x030 => pub uartcr: ReadWrite<u32, CR::Register>),
+        (0x034 => pub uartifls: ReadWrite<u32, FIFO::Register>),
+        (0x038 => pub uartimsc: ReadWrite<u32, INTERRUPT::Register>),
+        (0x03c => pub uartris: ReadOnly<u32, INTERRUPT::Register>),
+        (0x040 => pub uartmis: ReadOnly<u32, INTERRUPT::Register>),
+        (0x044 => pub uarticr: WriteOnly<u32, ICR::Register>),
+        (0x048 => pub uartdmacr: ReadWrite<u32, DMACR::Register>),
+        (0x04c => _reserved2),
+        (0xfe0 => pub uartperiphid: [ReadOnly<u32>; 4]),
+        (0xff0 => pub uartpcellid: [ReadOnly<u32>; 4]),
+        (0x1000 => @END),
+    }
+}
+
+// register_structs! 已经按声明的偏移检查了各字段的位置，这里再按 PL011 TRM
+// 固定整个寄存器块的大小和关键寄存器的偏移，防止修改寄存器表时整体错位
+const _: () = {
+    use core::mem::{offset_of, size_of};
+    assert!(size_of::<UartRegs>() == 0x1000);
+    assert!(offset_of!(UartRegs, uartdr) == 0x000);
+    assert!(offset_of!(UartRegs, uartfr) == 0x018);
+    assert!(offset_of!(UartRegs, uartibrd) == 0x024);
+    assert!(offset_of!(UartRegs, uartfbrd) == 0x028);
+    assert!(offset_of!(UartRegs, uartlcrh) == 0x02c);
+    assert!(offset_of!(UartRegs, uartcr) == 0x030);
+    assert!(offset_of!(UartRegs, uartimsc) == 0x038);
+    assert!(offset_of!(UartRegs, uarticr) == 0x044);
+    assert!(offset_of!(UartRegs, uartdmacr) == 0x048);
+    assert!(offset_of!(UartRegs, uartperiphid) == 0xfe0);
+    assert!(offset_of!(UartRegs, uartpcellid) == 0xff0);
+};
+
+register_bitfields![u32,
+    // 写入时硬件忽略第 8 位以上，但驱动总是通过 RAW 写入，
+    // 保证数据不会越过 8 位落到错误标志的位置
+    pub DATA [
+        RAW OFFSET(0) NUMBITS(8),
+        FE OFFSET(8) NUMBITS(1),
+        PE OFFSET(9) NUMBITS(1),
+        BE OFFSET(10) NUMBITS(1),
+        OE OFFSET(11) NUMBITS(1),
+    ],
+    pub FLAG [
+        CTS OFFSET(0) NUMBITS(1),
+        DSR OFFSET(1) NUMBITS(1),
+        DCD OFFSET(2) NUMBITS(1),
+        BUSY OFFSET(3) NUMBITS(1),
+        RXFE OFFSET(4) NUMBITS(1),
+        TXFF OFFSET(5) NUMBITS(1),
+        RXFF OFFSET(6) NUMBITS(1),
+        TXFE OFFSET(7) NUMBITS(1),
+    ],
+    pub LCRH [
+        BRK OFFSET(0) NUMBITS(1) [],
+        PEN OFFSET(1) NUMBITS(1) [],
+        EPS OFFSET(2) NUMBITS(1) [],
+        STP2 OFFSET(3) NUMBITS(1) [],
+        FEN OFFSET(4) NUMBITS(1) [],
+        WLEN OFFSET(5) NUMBITS(2) [
+            Five = 0,
+            Six = 1,
+            Seven = 2,
+            Eight = 3,
+        ],
+        SPS OFFSET(7) NUMBITS(1) [],
+    ],
+    pub CR [
+        UARTEN OFFSET(0) NUMBITS(1) [],
+        SIREN OFFSET(1) NUMBITS(1) [],
+        SIRLP OFFSET(2) NUMBITS(1) [],
+        LBE OFFSET(7) NUMBITS(1) [],
+        TXE OFFSET(8) NUMBITS(1) [],
+        RXE OFFSET(9) NUMBITS(1) [],
+        DTR OFFSET(10) NUMBITS(1) [],
+        RTS OFFSET(11) NUMBITS(1) [],
+        OUT1 OFFSET(12) NUMBITS(1) [],
+        OUT2 OFFSET(13) NUMBITS(1) [],
+        RTSEN OFFSET(14) NUMBITS(1) [],
+        CTSEN OFFSET(15) NUMBITS(1) [],
+    ],
+    pub FIFO [
+        TXSEL OFFSET(0) NUMBITS(3) [
+            TX1_8 = 0,
+            TX1_4 = 1,
+            TX1_2 = 2,
+            TX3_4 = 3,
+            TX7_8 = 4,
+        ],
+        RXSEL OFFSET(3) NUMBITS(3) [
+            RX1_8 = 0,
+            RX1_4 = 1,
+            RX1_2 = 2,
+            RX3_4 = 3,
+            RX7_8 = 4,
+        ],
+    ],
+    // uartimsc 的屏蔽位，uartris/uartmis 中相同位置是对应的原始/屏蔽后中断状态
+    pub INTERRUPT [
+        RIMIM OFFSET(0) NUMBITS(1) [],
+        CTSMIM OFFSET(1) NUMBITS(1) [],
+        DCDMIM OFFSET(2) NUMBITS(1) [],
+        DSRMIM OFFSET(3) NUMBITS(1) [],
+        RXIM OFFSET(4) NUMBITS(1) [],
+        TXIM OFFSET(5) NUMBITS(1) [],
+        RTIM OFFSET(6) NUMBITS(1) [],
+        FEIM OFFSET(7) NUMBITS(1) [],
+        PEIM OFFSET(8) NUMBITS(1) [],
+        BEIM OFFSET(9) NUMBITS(1) [],
+        OEIM OFFSET(10) NUMBITS(1) [],
+    ],
+    pub DMACR [
+        RXDMAE OFFSET(0) NUMBITS(1) [],
+        TXDMAE OFFSET(1) NUMBITS(1) [],
+        DMAONERR OFFSET(2) NUMBITS(1) [],
+    ],
+    pub ICR [
+        RIMIC OFFSET(0) NUMBITS(1) [],
+        CTSMIC OFFSET(1) NUMBITS(1) [],
+        DCDMIC OFFSET(2) NUMBITS(1) [],
+        DSRMIC OFFSET(3) NUMBITS(1) [],
+        RXIC OFFSET(4) NUMBITS(1) [],
+        TXIC OFFSET(5) NUMBITS(1) [],
+        RTIC OFFSET(6) NUMBITS(1) [],
+        FEIC OFFSET(7) NUMBITS(1) [],
+        PEIC OFFSET(8) NUMBITS(1) [],
+        BEIC OFFSET(9) NUMBITS(1) [],
+        OEIC OFFSET(10) NUMBITS(1) [],
+    ]
+];
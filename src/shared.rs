@@ -0,0 +1,112 @@
+//! 任务与中断处理共享 `Uart` 的容器，需要启用 `critical-section` feature。
+//!
+//! 初始化在临界区内完成，之后任务和中断处理都只通过共享引用访问 `Uart`，
+//! 不再需要用 `&mut` 在中断中别名访问。
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(feature = "async")]
+use crate::pl011::{ReadError, ReadFuture, WriteFuture};
+use crate::{
+    pl011::Uart,
+    wake::{DefaultWakeSource, WakeSource},
+};
+
+/// 可以放在 `static` 中、由任务和中断处理共享的 `Uart`。
+///
+/// ```ignore
+/// static UART: SharedUart = SharedUart::new();
+///
+/// let uart = Uart::new(base);
+/// uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+/// assert!(UART.init(uart));
+///
+/// // 中断处理函数
+/// UART.handle_interrupt();
+///
+/// // 任务
+/// UART.write(b"Hello, async World!").await;
+/// ```
+pub struct SharedUart<const DEPTH: usize = 16, W: WakeSource = DefaultWakeSource> {
+    uart: UnsafeCell<Option<Uart<DEPTH, W>>>,
+    ready: AtomicBool,
+}
+
+// 初始化之后 `uart` 只会被共享访问，`Uart` 本身是 `Sync` 的
+unsafe impl<const DEPTH: usize, W: WakeSource> Sync for SharedUart<DEPTH, W> {}
+
+impl<const DEPTH: usize, W: WakeSource> Default for SharedUart<DEPTH, W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> SharedUart<DEPTH, W> {
+    pub const fn new() -> Self {
+        Self {
+            uart: UnsafeCell::new(None),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// 放入配置好的 `Uart`，只能调用一次，重复调用时丢弃 `uart` 并返回 `false`。
+    pub fn init(&self, uart: Uart<DEPTH, W>) -> bool {
+        critical_section::with(|_| {
+            if self.ready.load(Ordering::Acquire) {
+                return false;
+            }
+            // 临界区内没有其他访问者：ready 置位前没有任何引用被交出
+            unsafe { *self.uart.get() = Some(uart) };
+            self.ready.store(true, Ordering::Release);
+            true
+        })
+    }
+
+    /// 已初始化时返回共享的 `Uart`
+    pub fn get(&self) -> Option<&Uart<DEPTH, W>> {
+        if !self.ready.load(Ordering::Acquire) {
+            return None;
+        }
+        unsafe { (*self.uart.get()).as_ref() }
+    }
+
+    /// 中断处理，未初始化时忽略
+    pub fn handle_interrupt(&self) {
+        if let Some(uart) = self.get() {
+            uart.handle_interrupt();
+        }
+    }
+
+    /// 异步发送数据，语义同 `Uart::write`。
+    ///
+    /// 未初始化时 panic。同一时刻应只有一个任务在发送，否则数据会交错。
+    #[cfg(feature = "async")]
+    pub fn write<'a>(&'a self, data: &'a [u8]) -> impl Future<Output = usize> + 'a {
+        WriteFuture {
+            uart: self.get().expect("SharedUart used before init"),
+            data,
+            index: 0,
+            cooperative: false,
+            completed: false,
+        }
+    }
+
+    /// 异步接收数据直到填满 `buf`，语义同 `Uart::read`。
+    ///
+    /// 未初始化时 panic。同一时刻应只有一个任务在接收。
+    #[cfg(feature = "async")]
+    pub fn read<'a>(
+        &'a self,
+        buf: &'a mut [u8],
+    ) -> impl Future<Output = Result<usize, ReadError>> + 'a {
+        ReadFuture {
+            uart: self.get().expect("SharedUart used before init"),
+            buf,
+            index: 0,
+            completed: None,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! SLIP（RFC 1055）帧编解码，需要启用 `slip` feature。
+//!
+//! 帧以 END 结尾，帧内的 END 和 ESC 被转义为两字节序列。
+
+use crate::{
+    pl011::{ReadError, Uart},
+    wake::WakeSource,
+};
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+/// SLIP 帧读取错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlipError {
+    /// 底层接收错误
+    Read(ReadError),
+    /// 帧超过缓冲区长度，该帧已被读完丢弃
+    FrameTooLong,
+    /// ESC 之后不是 ESC_END 或 ESC_ESC，该帧已被读完丢弃
+    InvalidEscape,
+}
+
+impl From<ReadError> for SlipError {
+    fn from(err: ReadError) -> Self {
+        Self::Read(err)
+    }
+}
+
+impl<const DEPTH: usize, W: WakeSource> Uart<DEPTH, W> {
+    /// 转义 `payload` 后作为一帧发送。
+    ///
+    /// 帧前先发送一个 END，把线路噪声与之前的数据隔开。不需要转义的连续字节一次写入。
+    /// 设置了前导码时在 END 之前发送，接收方会把它当作噪声丢弃。
+    pub async fn write_slip_frame(&mut self, payload: &[u8]) {
+        self.write_preamble().await;
+        self.write(&[END]).await;
+        let mut rest = payload;
+        while let Some(n) = rest.iter().position(|&b| b == END || b == ESC) {
+            self.write(&rest[..n]).await;
+            let escaped = if rest[n] == END { ESC_END } else { ESC_ESC };
+            self.write(&[ESC, escaped]).await;
+            rest = &rest[n + 1..];
+        }
+        self.write(rest).await;
+        self.write(&[END]).await;
+    }
+
+    /// 接收一帧直到 END，去掉转义后写入 `buf`，返回帧长度。
+    ///
+    /// 连续的 END 产生的空帧会被跳过，因此中途开始接收时会在下一个 END 处重新对齐。
+    /// 帧超过 `buf` 或含有非法转义时继续读到 END，再返回对应的错误。
+    pub async fn read_slip_frame(&mut self, buf: &mut [u8]) -> Result<usize, SlipError> {
+        let mut len = 0;
+        let mut escaped = false;
+        let mut error = None;
+        loop {
+            let mut byte = [0];
+            self.read(&mut byte).await?;
+            let data = match (escaped, byte[0]) {
+                (false, END) if len == 0 && error.is_none() => continue,
+                (false, END) => return error.map_or(Ok(len), Err),
+                (false, ESC) => {
+                    escaped = true;
+                    continue;
+                }
+                (false, byte) => byte,
+                (true, ESC_END) => END,
+                (true, ESC_ESC) => ESC,
+                (true, END) => return Err(error.unwrap_or(SlipError::InvalidEscape)),
+                (true, _) => {
+                    error.get_or_insert(SlipError::InvalidEscape);
+                    escaped = false;
+                    continue;
+                }
+            };
+            escaped = false;
+            match buf.get_mut(len) {
+                Some(slot) => {
+                    *slot = data;
+                    len += 1;
+                }
+                None => {
+                    error.get_or_insert(SlipError::FrameTooLong);
+                }
+            }
+        }
+    }
+}
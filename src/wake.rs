@@ -0,0 +1,50 @@
+//! waker 存储抽象，允许替换为执行器自带的通知机制。
+
+use core::task::Waker;
+
+/// 保存等待硬件事件的任务，并在中断中唤醒它。
+///
+/// 中断处理和 future 会在不同上下文中同时访问同一个实例，实现必须是中断安全的。
+/// 默认实现为 `futures::task::AtomicWaker`。
+pub trait WakeSource: Send + Sync {
+    /// 初始值，用于 `const fn Uart::new`
+    const INIT: Self;
+
+    /// 登记等待的任务，替换之前登记的任务
+    fn register(&self, waker: &Waker);
+
+    /// 唤醒已登记的任务
+    fn wake(&self);
+}
+
+#[cfg(feature = "async")]
+impl WakeSource for futures::task::AtomicWaker {
+    const INIT: Self = futures::task::AtomicWaker::new();
+
+    fn register(&self, waker: &Waker) {
+        futures::task::AtomicWaker::register(self, waker);
+    }
+
+    fn wake(&self) {
+        futures::task::AtomicWaker::wake(self);
+    }
+}
+
+/// 不保存任何任务，只使用阻塞接口时的默认实现
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoWake;
+
+impl WakeSource for NoWake {
+    const INIT: Self = NoWake;
+
+    fn register(&self, _waker: &Waker) {}
+
+    fn wake(&self) {}
+}
+
+/// `Uart` 默认使用的 `WakeSource`
+#[cfg(feature = "async")]
+pub type DefaultWakeSource = futures::task::AtomicWaker;
+/// `Uart` 默认使用的 `WakeSource`
+#[cfg(not(feature = "async"))]
+pub type DefaultWakeSource = NoWake;
@@ -5,21 +5,54 @@
 extern crate alloc;
 extern crate bare_test;
 
+// 启用 `critical-section` feature 时由测试程序提供实现：屏蔽本核的 IRQ
+#[cfg(feature = "critical-section")]
+mod cs_impl {
+    use aarch64_cpu::registers::{DAIF, ReadWriteable, Readable, Writeable};
+
+    struct DaifCriticalSection;
+    critical_section::set_impl!(DaifCriticalSection);
+
+    unsafe impl critical_section::Impl for DaifCriticalSection {
+        unsafe fn acquire() -> critical_section::RawRestoreState {
+            let daif = DAIF.get();
+            DAIF.modify(DAIF::I::Masked);
+            daif
+        }
+
+        unsafe fn release(daif: critical_section::RawRestoreState) {
+            DAIF.set(daif);
+        }
+    }
+}
+
 // spinlock and interrupt
 #[bare_test::tests]
 mod tests {
+    use alloc::{sync::Arc, task::Wake};
     use bare_test::{
         GetIrqConfig,
         globals::{PlatformInfoKind, global_val},
-        io::print,
         irq::{IrqHandleResult, IrqParam},
         mem::iomap,
         println,
     };
+    use core::cell::UnsafeCell;
+    use core::mem::offset_of;
     use core::ops::{Deref, DerefMut};
-    use core::{cell::UnsafeCell, str};
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+    use core::task::{Context, Poll, Waker};
     use log::info;
-    use pl011::Uart;
+    use pl011::framed::{Endian, PrefixFormat};
+    use pl011::line::{Checksum, Utf8Error};
+    use pl011::regs::FLAG;
+    use pl011::{
+        Baud, BufferedUart, ClockHz, ControlConfig, DataBits, Error, FifoLevel, FlowControl,
+        INTERRUPT, InitError, InterruptStatus, InvalidBaud, LineConfig, Parity, ReadError,
+        SharedUart, StopBits, TimedOut, Uart, UartRegs, WriteError, baud_divisor, compute_divisor,
+        decode_mis,
+    };
     pub const BAUD_RATE: u32 = 115200; // 波特率
     pub const CLK_RATE: u32 = 100_000_000; // 时钟频率
 
@@ -86,6 +119,93 @@ mod tests {
 
     static UART: Mutex<Option<Uart>> = Mutex::new(None);
 
+    // 用内存模拟的寄存器块，读写没有硬件副作用，测试通过修改寄存器值模拟硬件状态
+    #[repr(align(4096))]
+    struct MockRegs(UnsafeCell<[u32; 1024]>);
+
+    unsafe impl Sync for MockRegs {}
+
+    impl MockRegs {
+        const fn new() -> Self {
+            Self(UnsafeCell::new([0; 1024]))
+        }
+
+        fn uart(&self) -> Uart {
+            Uart::new(self.0.get() as *mut u8)
+        }
+
+        fn get(&self, offset: usize) -> u32 {
+            unsafe { (self.0.get() as *const u32).add(offset / 4).read_volatile() }
+        }
+
+        fn set(&self, offset: usize, value: u32) {
+            unsafe {
+                (self.0.get() as *mut u32)
+                    .add(offset / 4)
+                    .write_volatile(value)
+            }
+        }
+    }
+
+    // 偏移和位值取自驱动的寄存器表，与驱动使用同一份定义
+    const UARTFR: usize = offset_of!(UartRegs, uartfr);
+    const UARTIBRD: usize = offset_of!(UartRegs, uartibrd);
+    const UARTLCRH: usize = offset_of!(UartRegs, uartlcrh);
+    const UARTCR: usize = offset_of!(UartRegs, uartcr);
+    const UARTIFLS: usize = offset_of!(UartRegs, uartifls);
+    const UARTIMSC: usize = offset_of!(UartRegs, uartimsc);
+    const UARTRIS: usize = offset_of!(UartRegs, uartris);
+    const UARTMIS: usize = offset_of!(UartRegs, uartmis);
+    const UARTICR: usize = offset_of!(UartRegs, uarticr);
+    const FR_BUSY: u32 = FLAG::BUSY.mask << FLAG::BUSY.shift;
+    const FR_RXFE: u32 = FLAG::RXFE.mask << FLAG::RXFE.shift;
+    const FR_TXFF: u32 = FLAG::TXFF.mask << FLAG::TXFF.shift;
+    const FR_RXFF: u32 = FLAG::RXFF.mask << FLAG::RXFF.shift;
+    const FR_TXFE: u32 = FLAG::TXFE.mask << FLAG::TXFE.shift;
+    const INT_CTS: u32 = INTERRUPT::CTSMIM.mask << INTERRUPT::CTSMIM.shift;
+    const INT_RX: u32 = INTERRUPT::RXIM.mask << INTERRUPT::RXIM.shift;
+    const INT_TX: u32 = INTERRUPT::TXIM.mask << INTERRUPT::TXIM.shift;
+    const INT_RT: u32 = INTERRUPT::RTIM.mask << INTERRUPT::RTIM.shift;
+    const INT_OE: u32 = INTERRUPT::OEIM.mask << INTERRUPT::OEIM.shift;
+
+    // 记录被唤醒次数的 waker
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // 从设备树找到 PL011 并映射寄存器
+    fn map_uart() -> Uart {
+        let PlatformInfoKind::DeviceTree(fdt) = &global_val().platform_info;
+        let fdt = fdt.get();
+        let node = fdt.find_compatible(&["arm,pl011"]).next().unwrap();
+        let reg = node.reg().unwrap().next().unwrap();
+        let size = reg.size.unwrap();
+        let mmio = iomap((reg.address as usize).into(), size);
+        Uart::from_addr(mmio.as_ptr() as usize, size).unwrap()
+    }
+
+    // 关闭中断并打开回环，用于不依赖外部连线的收发测试
+    fn loopback_uart() -> Uart {
+        let uart = map_uart();
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+        uart.set_interrupt_mask(
+            INTERRUPT::RXIM::CLEAR + INTERRUPT::TXIM::CLEAR + INTERRUPT::RTIM::CLEAR,
+        );
+        uart.set_loopback(true);
+        uart
+    }
+
+    // 由通用定时器换算的单调纳秒时钟，供需要按位时间等待的测试使用
+    fn now_ns() -> u64 {
+        use aarch64_cpu::registers::{CNTFRQ_EL0, CNTPCT_EL0, Readable};
+        let freq = CNTFRQ_EL0.get().max(1);
+        (CNTPCT_EL0.get() as u128 * 1_000_000_000 / freq as u128) as u64
+    }
+
     #[test]
     fn it_works() {
         info!("This is a test log message.");
@@ -103,7 +223,7 @@ mod tests {
         let base = reg.address;
         let mut mmio = iomap((base as usize).into(), reg.size.unwrap());
         let uart = unsafe { Uart::new(mmio.as_mut() as *mut u8) };
-        uart.init(BAUD_RATE, CLK_RATE);
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
         // 加锁，并通过括号自动drop锁
         {
             let mut pl011 = UART.lock();
@@ -133,8 +253,896 @@ mod tests {
         }
         println!("");
 
-        println!("irq count: {}", UART.lock().as_ref().unwrap().irq_conut);
+        println!("irq count: {}", UART.lock().as_ref().unwrap().irq_count());
 
         println!("test passed!");
     }
+
+    static READ_SPLIT: MockRegs = MockRegs::new();
+
+    #[test]
+    fn read_respects_buffer_len() {
+        // 模拟寄存器没有 FIFO：RXFE 清除时每次读取都得到同一个字节，相当于 FIFO 中一直有数据
+        READ_SPLIT.set(UARTFR, 0);
+        READ_SPLIT.set(0, 0x42);
+        let mut uart = READ_SPLIT.uart();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut buf = [0u8; 16];
+        let (first, rest) = buf.split_at_mut(8);
+        // 读满 8 字节即完成，不会越过切片边界多读
+        assert_eq!(pin!(uart.read(first)).poll(&mut cx), Poll::Ready(Ok(8)));
+        assert_eq!(rest, [0; 8]);
+        {
+            // FIFO 读空后第二次读取等待，有数据后同样只读 8 字节
+            READ_SPLIT.set(UARTFR, FR_RXFE);
+            let mut read = pin!(uart.read(rest));
+            assert_eq!(read.as_mut().poll(&mut cx), Poll::Pending);
+            READ_SPLIT.set(UARTFR, 0);
+            assert_eq!(read.poll(&mut cx), Poll::Ready(Ok(8)));
+        }
+        assert_eq!(buf, [0x42; 16]);
+        // 非阻塞读取同样以缓冲区长度为界
+        assert_eq!(uart.try_read(&mut [0; 8]), 8);
+        READ_SPLIT.set(UARTFR, FR_RXFE);
+        assert_eq!(uart.try_read(&mut [0; 8]), 0);
+    }
+
+    #[test]
+    fn async_read_receives_loopback() {
+        let mut uart = loopback_uart();
+        uart.write_blocking(b"abc").unwrap();
+        uart.flush().unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(spin_on::spin_on(uart.read(&mut buf)), Ok(2));
+        assert_eq!(&buf, b"ab");
+        assert_eq!(uart.receive(), b'c');
+        uart.set_loopback(false);
+    }
+
+    #[test]
+    fn read_reports_break() {
+        let mut uart = loopback_uart();
+        // 回环下发送 break，线路至少保持低电平一帧以上，接收端才会收到带 BE 标志的字节
+        uart.set_break(true);
+        uart.wait_bits(20, now_ns);
+        uart.set_break(false);
+        uart.wait_bits(10, now_ns);
+        // 中断中记录的错误与 FIFO 中带标志的字节是同一个 break，只报告一次
+        uart.handle_interrupt();
+
+        let mut buf = [0u8; 4];
+        let res = spin_on::spin_on(uart.read(&mut buf));
+        assert_eq!(res, Err(ReadError::Break));
+
+        // 出错的字节已经读出，之后没有数据也没有错误
+        let mut cx = Context::from_waker(Waker::noop());
+        {
+            let mut read = pin!(uart.read(&mut buf));
+            assert_eq!(read.as_mut().poll(&mut cx), Poll::Pending);
+        }
+        uart.set_loopback(false);
+    }
+
+    #[test]
+    fn current_config_round_trip() {
+        let uart = map_uart();
+        let line = LineConfig {
+            data_bits: DataBits::Seven,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+        };
+        uart.init_with(ClockHz(CLK_RATE), Baud(BAUD_RATE), line)
+            .unwrap();
+        let cfg = uart.current_config();
+        assert_eq!(cfg.line, line);
+        assert!(cfg.fifo_enabled && cfg.uart_enabled && cfg.tx_enabled && cfg.rx_enabled);
+        assert_eq!(
+            cfg.divisor,
+            baud_divisor(ClockHz(CLK_RATE), Baud(BAUD_RATE))
+        );
+        assert_eq!(
+            baud_divisor(ClockHz(CLK_RATE), cfg.baud(ClockHz(CLK_RATE))),
+            cfg.divisor
+        );
+        // 恢复 8N1，避免影响后续输出
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+    }
+
+    #[test]
+    fn baud_divisor_carries_fraction() {
+        // 20_262_000 / (16 * 115200) = 10.9928...，小数部分舍入到 64/64
+        assert_eq!(baud_divisor(ClockHz(20_262_000), Baud(115200)), (11, 0));
+        assert_eq!(baud_divisor(ClockHz(CLK_RATE), Baud(BAUD_RATE)), (54, 16));
+    }
+
+    #[test]
+    fn nmea_checksum() {
+        let body = b"GPGLL,4916.45,N,12311.12,W,225444,A,";
+        assert_eq!(Checksum::Xor.compute(body), 0x1D);
+        assert_eq!(Checksum::Additive.compute(&[0xF0, 0x20]), 0x10);
+    }
+
+    #[test]
+    fn high_baud_divisors() {
+        let clk = ClockHz(CLK_RATE);
+        for (baud, divisor) in [
+            (921_600, (6, 50)),
+            (1_500_000, (4, 11)),
+            (3_000_000, (2, 5)),
+        ] {
+            let d = compute_divisor(clk, Baud(baud)).unwrap();
+            assert_eq!((d.integer, d.fraction), divisor);
+            assert!(d.error_ppm.abs() < 3000, "{baud}: {} ppm", d.error_ppm);
+        }
+        assert_eq!(
+            compute_divisor(clk, Baud(7_000_000)),
+            Err(InvalidBaud::TooHigh)
+        );
+        assert_eq!(compute_divisor(clk, Baud(0)), Err(InvalidBaud::Zero));
+    }
+
+    static UTF8_SPLIT: MockRegs = MockRegs::new();
+
+    #[test]
+    fn read_str_keeps_split_characters() {
+        // 模拟的数据寄存器一直能读出同一个字节，每次调用前换成下一个字节
+        UTF8_SPLIT.set(UARTFR, FR_TXFE);
+        let mut uart = UTF8_SPLIT.uart();
+        // "é" = C3 A9：单字节缓冲区只收到首字节，暂存起来，返回空串
+        UTF8_SPLIT.set(0, 0xc3);
+        let mut one = [0u8; 1];
+        assert_eq!(spin_on::spin_on(uart.read_str(&mut one)), Ok(""));
+        // 缓冲区放不下暂存字节和下一个字节：报错而不是一直返回空串，暂存的首字节不丢
+        assert_eq!(
+            spin_on::spin_on(uart.read_str(&mut one)),
+            Err(Utf8Error::BufferTooSmall)
+        );
+        // 换用更大的缓冲区后，暂存的首字节与新收到的字节拼成完整字符
+        UTF8_SPLIT.set(0, 0xa9);
+        let mut two = [0u8; 2];
+        assert_eq!(spin_on::spin_on(uart.read_str(&mut two)), Ok("é"));
+    }
+
+    static LOW_BAUD: MockRegs = MockRegs::new();
+
+    #[test]
+    fn low_baud_is_rejected() {
+        // 100 MHz / (16 * 75) ≈ 83333，超出 uartibrd 的 16 位
+        let clk = ClockHz(CLK_RATE);
+        assert_eq!(compute_divisor(clk, Baud(75)), Err(InvalidBaud::TooLow));
+        // 分频值正好为 0xFFFF 时可用，再大半个分频单位就超出了 TRM 允许的范围
+        let d = compute_divisor(ClockHz(16 * 0xFFFF), Baud(1)).unwrap();
+        assert_eq!((d.integer, d.fraction), (0xFFFF, 0));
+        assert_eq!(
+            compute_divisor(ClockHz(16 * 0xFFFF + 8), Baud(1)),
+            Err(InvalidBaud::TooLow)
+        );
+        LOW_BAUD.set(UARTFR, FR_TXFE);
+        LOW_BAUD.set(UARTIBRD, 54);
+        let uart = LOW_BAUD.uart();
+        assert_eq!(uart.set_baud(clk, Baud(75)), Err(Error::InvalidBaud));
+        assert_eq!(LOW_BAUD.get(UARTIBRD), 54);
+    }
+
+    static SLOW_DRAIN: MockRegs = MockRegs::new();
+    static SLOW_DRAIN_SPINS: AtomicUsize = AtomicUsize::new(0);
+
+    // 每次自旋推进一步：FIFO 在第 3 次自旋后清空，移位寄存器在第 6 次自旋后空闲
+    fn slow_drain_step() {
+        // 还在等待发送完成时 UART 不能被关闭
+        assert_ne!(SLOW_DRAIN.get(UARTCR), 0);
+        let spins = SLOW_DRAIN_SPINS.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fr = SLOW_DRAIN.get(UARTFR);
+        if spins >= 3 {
+            fr |= FR_TXFE;
+        }
+        if spins >= 6 {
+            fr &= !FR_BUSY;
+        }
+        SLOW_DRAIN.set(UARTFR, fr);
+    }
+
+    #[test]
+    fn disable_waits_for_slow_drain() {
+        SLOW_DRAIN.set(UARTFR, FR_BUSY);
+        SLOW_DRAIN.set(UARTCR, 0x301);
+        let mut uart = SLOW_DRAIN.uart();
+        uart.set_spin_hook(slow_drain_step);
+        assert_eq!(uart.disable(), Ok(()));
+        assert_eq!(SLOW_DRAIN_SPINS.load(Ordering::Relaxed), 6);
+        assert_eq!(SLOW_DRAIN.get(UARTCR), 0);
+
+        // FIFO 一直不清空时在自旋上限处放弃，但仍然关闭 UART
+        SLOW_DRAIN.set(UARTFR, FR_BUSY);
+        SLOW_DRAIN.set(UARTCR, 0x301);
+        let mut uart = SLOW_DRAIN.uart();
+        uart.set_spin_cap(10);
+        assert_eq!(uart.disable(), Err(TimedOut));
+        assert_eq!(SLOW_DRAIN.get(UARTCR), 0);
+    }
+
+    static SPLIT_A: MockRegs = MockRegs::new();
+    static SPLIT_B: MockRegs = MockRegs::new();
+
+    #[test]
+    fn reunite_rejects_foreign_half() {
+        let (tx_a, rx_a) = SPLIT_A.uart().split();
+        let (tx_b, rx_b) = SPLIT_B.uart().split();
+        let Err(err) = tx_a.reunite(rx_b) else {
+            panic!("halves of different UARTs were reunited");
+        };
+        let Ok(uart) = err.tx.reunite(rx_a) else {
+            panic!("halves of the same UART were rejected");
+        };
+        assert_eq!(uart.base.as_ptr() as usize, SPLIT_A.0.get() as usize);
+        assert!(tx_b.reunite(err.rx).is_ok());
+    }
+
+    static BOTH_IRQ: MockRegs = MockRegs::new();
+
+    #[test]
+    fn interrupt_wakes_both_directions() {
+        // 接收 FIFO 为空、发送 FIFO 已满，读写 future 都会挂起并登记 waker
+        BOTH_IRQ.set(UARTFR, FR_RXFE | FR_TXFF);
+        let mut uart = BOTH_IRQ.uart();
+        let rx_woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let tx_woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let rx_waker = Waker::from(rx_woken.clone());
+        let tx_waker = Waker::from(tx_woken.clone());
+        let mut buf = [0u8; 1];
+        let read = pin!(uart.read(&mut buf)).poll(&mut Context::from_waker(&rx_waker));
+        assert!(read.is_pending());
+        let write = pin!(uart.write(b"x")).poll(&mut Context::from_waker(&tx_waker));
+        assert!(write.is_pending());
+
+        // 数据到达的同时发送 FIFO 腾出空间，RXMIS 和 TXMIS 同时挂起
+        BOTH_IRQ.set(UARTFR, 0);
+        BOTH_IRQ.set(UARTRIS, INT_RX | INT_TX);
+        BOTH_IRQ.set(UARTMIS, INT_RX | INT_TX);
+        uart.handle_interrupt();
+        assert_eq!(rx_woken.0.load(Ordering::Relaxed), 1);
+        assert_eq!(tx_woken.0.load(Ordering::Relaxed), 1);
+        assert_eq!(BOTH_IRQ.get(UARTICR), INT_RX | INT_TX);
+    }
+
+    static EMPTY_IO: MockRegs = MockRegs::new();
+
+    #[test]
+    fn empty_buffers_complete_immediately() {
+        // FIFO 状态会让非空的读写挂起
+        EMPTY_IO.set(UARTFR, FR_RXFE | FR_TXFF);
+        let mut uart = EMPTY_IO.uart();
+        let woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(pin!(uart.read(&mut [])).poll(&mut cx), Poll::Ready(Ok(0)));
+        assert_eq!(pin!(uart.write(b"")).poll(&mut cx), Poll::Ready(0));
+
+        // 没有登记 waker，中断不会唤醒任何任务
+        EMPTY_IO.set(UARTFR, 0);
+        EMPTY_IO.set(UARTMIS, INT_RX | INT_TX);
+        uart.handle_interrupt();
+        assert_eq!(woken.0.load(Ordering::Relaxed), 0);
+    }
+
+    static SAVED: MockRegs = MockRegs::new();
+
+    #[test]
+    fn restore_state_round_trip() {
+        let uart = SAVED.uart();
+        let line = LineConfig {
+            data_bits: DataBits::Seven,
+            parity: Parity::Odd,
+            stop_bits: StopBits::Two,
+        };
+        uart.init_with(ClockHz(CLK_RATE), Baud(9600), line).unwrap();
+        let state = uart.save_state();
+        let cfg = uart.current_config();
+
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+        uart.set_loopback(true);
+        assert_ne!(uart.current_config(), cfg);
+
+        uart.restore_state(&state);
+        assert_eq!(uart.current_config(), cfg);
+        assert_eq!(uart.save_state(), state);
+    }
+
+    static RECONFIG: MockRegs = MockRegs::new();
+
+    #[test]
+    fn reconfigure_applies_all_settings() {
+        let uart = RECONFIG.uart();
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+        uart.set_loopback(true);
+        let mut cfg = uart.current_config();
+        cfg.line = LineConfig {
+            data_bits: DataBits::Seven,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+        };
+        cfg.divisor = baud_divisor(ClockHz(CLK_RATE), Baud(9600));
+        cfg.rx_enabled = false;
+        uart.reconfigure(cfg).unwrap();
+        assert_eq!(uart.current_config(), cfg);
+        // UartConfig 之外的控制位保持不变
+        assert_ne!(RECONFIG.get(UARTCR) & (1 << 7), 0);
+
+        // 无效的分频值不修改任何寄存器
+        let state = uart.save_state();
+        cfg.divisor = (0, 5);
+        assert_eq!(uart.reconfigure(cfg), Err(InitError::BaudTooHigh));
+        cfg.divisor = (26, 64);
+        assert_eq!(uart.reconfigure(cfg), Err(InitError::InvalidFraction));
+        cfg.divisor = (0xFFFF, 1);
+        assert_eq!(uart.reconfigure(cfg), Err(InitError::BaudTooLow));
+        assert_eq!(uart.save_state(), state);
+    }
+
+    static HIGH_TRIGGER: MockRegs = MockRegs::new();
+
+    #[test]
+    fn small_write_with_high_tx_trigger() {
+        // FIFO 中还有数据但未满，触发水位为 7/8：少量数据不应等待发送中断
+        HIGH_TRIGGER.set(UARTFR, 0);
+        let mut uart = HIGH_TRIGGER.uart();
+        uart.set_fifo_levels(FifoLevel::SevenEighths, FifoLevel::Half);
+        let woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(pin!(uart.write(b"abc")).poll(&mut cx), Poll::Ready(3));
+        assert_eq!(HIGH_TRIGGER.get(0), b'c' as u32);
+    }
+
+    #[test]
+    fn decode_mis_names_pending_interrupts() {
+        assert_eq!(decode_mis(0), InterruptStatus::default());
+        let irq = decode_mis((1 << 4) | (1 << 6));
+        assert!(irq.rx && irq.rx_timeout && !irq.tx);
+        let irq = decode_mis((1 << 1) | (1 << 5) | (1 << 9) | (1 << 10));
+        assert_eq!(
+            irq,
+            InterruptStatus {
+                tx: true,
+                break_: true,
+                overrun: true,
+                cts: true,
+                ..Default::default()
+            }
+        );
+        let irq = decode_mis((1 << 7) | (1 << 8));
+        assert!(irq.framing && irq.parity && !irq.rx);
+    }
+
+    static RESERVED: MockRegs = MockRegs::new();
+
+    #[test]
+    fn save_state_ignores_reserved_offsets() {
+        // 保留区域填入垃圾值，保存的状态只来自已定义的寄存器
+        for offset in [0x08, 0x0c, 0x10, 0x14, 0x1c, 0x20, 0x4c] {
+            RESERVED.set(offset, 0xdead_beef);
+        }
+        RESERVED.set(UARTIBRD, 54);
+        RESERVED.set(UARTCR, 0x301);
+        let uart = RESERVED.uart();
+        let state = uart.save_state();
+        RESERVED.set(UARTIBRD, 0);
+        RESERVED.set(UARTCR, 0);
+        uart.restore_state(&state);
+        assert_eq!(RESERVED.get(UARTIBRD), 54);
+        assert_eq!(RESERVED.get(UARTCR), 0x301);
+        // 恢复时也不写保留区域
+        assert_eq!(RESERVED.get(0x1c), 0xdead_beef);
+        assert_eq!(RESERVED.get(0x4c), 0xdead_beef);
+    }
+
+    // 早期控制台可以在编译期构造
+    static EARLY_CONSOLE: Uart = Uart::new_early(0x2800_d000);
+
+    #[test]
+    fn early_console_is_const() {
+        assert_eq!(EARLY_CONSOLE.base_addr(), 0x2800_d000);
+    }
+
+    static OVERRUN: MockRegs = MockRegs::new();
+
+    #[test]
+    fn overrun_reported_after_buffered_bytes() {
+        // FIFO 中有溢出前收到的有效数据，中断记录了溢出
+        OVERRUN.set(UARTFR, 0);
+        OVERRUN.set(0, b'a' as u32);
+        OVERRUN.set(UARTRIS, INT_OE);
+        let mut uart = OVERRUN.uart();
+        uart.handle_interrupt();
+        let mut buf = [0u8; 3];
+        assert_eq!(spin_on::spin_on(uart.read(&mut buf)), Ok(3));
+        assert_eq!(&buf, b"aaa");
+        // FIFO 读空后才报告溢出
+        OVERRUN.set(UARTFR, FR_RXFE);
+        assert_eq!(
+            spin_on::spin_on(uart.read(&mut buf)),
+            Err(ReadError::Overrun)
+        );
+    }
+
+    static FRAMED: MockRegs = MockRegs::new();
+
+    #[test]
+    fn read_framed_uses_length_prefix() {
+        // 模拟寄存器没有 FIFO，每次读取都得到同一个字节：长度 3，负载 3 个 3
+        FRAMED.set(UARTFR, 0);
+        FRAMED.set(0, 3);
+        let mut uart = FRAMED.uart();
+        let mut buf = [0u8; 4];
+        assert_eq!(spin_on::spin_on(uart.read_framed(&mut buf)), Ok(3));
+        assert_eq!(buf, [3, 3, 3, 0]);
+        let mut small = [0u8; 2];
+        assert_eq!(
+            spin_on::spin_on(uart.read_framed(&mut small)),
+            Err(Error::FrameTooLong)
+        );
+        // 大端 2 字节长度字段 0x0303 超过缓冲区
+        uart.set_frame_prefix(PrefixFormat::U16_BE).unwrap();
+        assert_eq!(
+            spin_on::spin_on(uart.read_framed(&mut buf)),
+            Err(Error::FrameTooLong)
+        );
+        // 发送时负载长度必须能放进长度字段
+        uart.set_frame_prefix(PrefixFormat::U8).unwrap();
+        assert_eq!(
+            spin_on::spin_on(uart.write_framed(&[0; 256])),
+            Err(Error::FrameTooLong)
+        );
+        assert_eq!(spin_on::spin_on(uart.write_framed(&[0; 255])), Ok(255));
+        assert_eq!(
+            uart.set_frame_prefix(PrefixFormat::new(3, Endian::Le)),
+            Err(Error::InvalidPrefix)
+        );
+    }
+
+    static WARM_BOOT: MockRegs = MockRegs::new();
+
+    #[test]
+    fn init_clears_stale_interrupt_status() {
+        // 热重启前遗留的接收、发送、接收超时和调制解调器中断状态
+        WARM_BOOT.set(UARTRIS, INT_CTS | INT_RX | INT_TX | INT_RT);
+        let uart = WARM_BOOT.uart();
+        uart.init_polling(ClockHz(CLK_RATE), Baud(BAUD_RATE))
+            .unwrap();
+        assert_eq!(WARM_BOOT.get(UARTICR), 0x7ff);
+    }
+
+    static WRITABLE: MockRegs = MockRegs::new();
+
+    #[test]
+    fn wait_writable_follows_txff() {
+        WRITABLE.set(UARTFR, FR_TXFF);
+        let uart = WRITABLE.uart();
+        let woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut ready = pin!(uart.wait_writable());
+        assert_eq!(ready.as_mut().poll(&mut cx), Poll::Pending);
+        WRITABLE.set(UARTFR, 0);
+        assert_eq!(ready.poll(&mut cx), Poll::Ready(()));
+    }
+
+    static WRITE_POLL: MockRegs = MockRegs::new();
+
+    #[test]
+    fn write_future_waits_while_fifo_full() {
+        WRITE_POLL.set(UARTFR, FR_TXFF);
+        let mut uart = WRITE_POLL.uart();
+        let mut cx = Context::from_waker(Waker::noop());
+        {
+            let mut write = pin!(uart.write(b"xyz"));
+            // FIFO 满时不写数据寄存器，每次 poll 记录一次背压
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(WRITE_POLL.get(0), 0);
+            // FIFO 有空间后逐字节写入，最后写入的是最后一个字节
+            WRITE_POLL.set(UARTFR, 0);
+            assert_eq!(write.poll(&mut cx), Poll::Ready(3));
+            assert_eq!(WRITE_POLL.get(0), b'z' as u32);
+        }
+        assert_eq!(uart.tx_backpressure_events(), 2);
+    }
+
+    static SIM_IO: MockRegs = MockRegs::new();
+
+    #[test]
+    fn simulated_interrupts_resolve_futures() {
+        SIM_IO.set(UARTFR, FR_RXFE | FR_TXFF | FR_BUSY);
+        SIM_IO.set(UARTIMSC, INT_RX | INT_TX);
+        // future 借用端口期间仍要调用模拟中断，通过 SharedUart 共享
+        let shared = SharedUart::default();
+        assert!(shared.init(SIM_IO.uart()));
+        let uart = shared.get().unwrap();
+        let woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        // 接收：没有数据时挂起，模拟收到的字节唤醒 future 并被读出
+        let mut buf = [0u8; 1];
+        {
+            let mut read = pin!(shared.read(&mut buf));
+            assert_eq!(read.as_mut().poll(&mut cx), Poll::Pending);
+            uart.simulate_rx(b'k');
+            assert_eq!(woken.0.load(Ordering::Relaxed), 1);
+            assert_eq!(read.poll(&mut cx), Poll::Ready(Ok(1)));
+        }
+        assert_eq!(&buf, b"k");
+
+        // 发送：FIFO 满时挂起，模拟发送完成后唤醒并写完
+        SIM_IO.set(UARTFR, FR_RXFE | FR_TXFF | FR_BUSY);
+        {
+            let mut write = pin!(shared.write(b"on"));
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            uart.simulate_tx_complete();
+            assert_eq!(woken.0.load(Ordering::Relaxed), 2);
+            assert_eq!(write.poll(&mut cx), Poll::Ready(2));
+        }
+        assert_eq!(SIM_IO.get(0), b'n' as u32);
+    }
+
+    static FIFO_MODE: MockRegs = MockRegs::new();
+
+    #[test]
+    fn rx_available_follows_fifo_mode() {
+        // 接收已满、发送为空：FIFO 模式下满为 DEPTH 字节，字符模式下只有 1 字节
+        FIFO_MODE.set(UARTFR, FR_RXFF | FR_TXFE);
+        let uart = FIFO_MODE.uart();
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+        assert_eq!(uart.rx_available(), Some(16));
+        uart.set_fifo_enabled(false);
+        assert_eq!(uart.rx_available(), Some(1));
+        uart.set_fifo_enabled(true);
+        assert_eq!(uart.rx_available(), Some(16));
+    }
+
+    static FIFO_WRITE: MockRegs = MockRegs::new();
+
+    #[test]
+    fn write_chunk_follows_fifo_mode() {
+        // 模拟的寄存器没有 FIFO，TXFE 一直置位；协作式写每遇到一次 TXFE 只写一块就让出，
+        // 数据寄存器中留下的是这一块的最后一个字节
+        let data = b"abcdefghijklmnopqrst";
+        FIFO_WRITE.set(UARTFR, FR_TXFE);
+        let mut uart = FIFO_WRITE.uart();
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        {
+            // FIFO 模式：一次写满 16 字节
+            let mut write = pin!(uart.write_cooperative(data));
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(FIFO_WRITE.get(0), b'p' as u32);
+            assert_eq!(write.poll(&mut cx), Poll::Ready(20));
+            assert_eq!(FIFO_WRITE.get(0), b't' as u32);
+        }
+
+        uart.set_fifo_enabled(false);
+        {
+            // 字符模式：每次 TXFE 只写 1 字节
+            let mut write = pin!(uart.write_cooperative(data));
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(FIFO_WRITE.get(0), b'a' as u32);
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(FIFO_WRITE.get(0), b'b' as u32);
+            // 保持寄存器满时不写入
+            FIFO_WRITE.set(UARTFR, FR_TXFF);
+            assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(FIFO_WRITE.get(0), b'b' as u32);
+            FIFO_WRITE.set(UARTFR, FR_TXFE);
+            for (i, &byte) in data.iter().enumerate().skip(2).take(17) {
+                assert_eq!(write.as_mut().poll(&mut cx), Poll::Pending, "byte {i}");
+                assert_eq!(FIFO_WRITE.get(0), byte as u32);
+            }
+            assert_eq!(write.poll(&mut cx), Poll::Ready(20));
+            assert_eq!(FIFO_WRITE.get(0), b't' as u32);
+        }
+    }
+
+    static PERIPH_ID: MockRegs = MockRegs::new();
+
+    #[test]
+    fn peripheral_id_decodes_pl011() {
+        // r1p5 的 PL011：UARTPeriphID0..3 = 0x11, 0x10, 0x34, 0x00
+        for (i, id) in [0x11, 0x10, 0x34, 0x00].into_iter().enumerate() {
+            PERIPH_ID.set(offset_of!(UartRegs, uartperiphid) + i * 4, id);
+        }
+        let uart = PERIPH_ID.uart();
+        let id = uart.peripheral_id();
+        assert!(id.is_pl011());
+        assert_eq!(id.revision, 3);
+        // PrimeCell ID 未设置时探测失败
+        assert!(!uart.probe());
+        for (i, id) in [0x0d, 0xf0, 0x05, 0xb1].into_iter().enumerate() {
+            PERIPH_ID.set(offset_of!(UartRegs, uartpcellid) + i * 4, id);
+        }
+        assert!(uart.probe());
+    }
+
+    static CTS_STALL: MockRegs = MockRegs::new();
+
+    #[test]
+    fn write_times_out_when_cts_stays_deasserted() {
+        // 发送 FIFO 满且 CTS 撤销，超时 0 个位时间：第一次轮询就应报告超时
+        CTS_STALL.set(UARTFR, FR_TXFF);
+        CTS_STALL.set(UARTCR, 0x301 | 1 << 15);
+        let mut uart = CTS_STALL.uart();
+        let woken = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(
+            pin!(uart.write_with_cts_timeout(b"abc", 0)).poll(&mut cx),
+            Poll::Ready(Err(WriteError::FlowControlTimeout { written: 0 }))
+        );
+    }
+
+    #[test]
+    fn fifo_level_thresholds() {
+        let table = [
+            (FifoLevel::OneEighth, 2, 4),
+            (FifoLevel::Quarter, 4, 8),
+            (FifoLevel::Half, 8, 16),
+            (FifoLevel::ThreeQuarters, 12, 24),
+            (FifoLevel::SevenEighths, 14, 28),
+        ];
+        for (level, depth16, depth32) in table {
+            assert_eq!(level.threshold_bytes(16), depth16);
+            assert_eq!(level.threshold_bytes(32), depth32);
+        }
+    }
+
+    static FLOW: MockRegs = MockRegs::new();
+
+    #[test]
+    fn flow_control_round_trip() {
+        FLOW.set(UARTFR, FR_TXFE);
+        FLOW.set(UARTCR, 0x301);
+        let uart = FLOW.uart();
+        for flow in [
+            FlowControl::RtsCts,
+            FlowControl::Cts,
+            FlowControl::Rts,
+            FlowControl::None,
+        ] {
+            uart.set_flow_control(flow);
+            assert_eq!(uart.flow_control(), flow);
+            // 其他控制位保持不变
+            assert_eq!(FLOW.get(UARTCR) & 0x301, 0x301);
+        }
+    }
+
+    static FORCED_DIVISOR: MockRegs = MockRegs::new();
+
+    #[test]
+    fn set_divisor_writes_registers() {
+        FORCED_DIVISOR.set(UARTFR, FR_TXFE);
+        FORCED_DIVISOR.set(UARTCR, 0x301);
+        let uart = FORCED_DIVISOR.uart();
+        uart.set_divisor(54, 16).unwrap();
+        assert_eq!(uart.divisor(), (54, 16));
+        assert_eq!(FORCED_DIVISOR.get(UARTCR), 0x301);
+        assert_eq!(uart.set_divisor(54, 64), Err(Error::InvalidBaud));
+        assert_eq!(uart.set_divisor(0, 1), Err(Error::InvalidBaud));
+        assert_eq!(uart.set_divisor(0xFFFF, 1), Err(Error::InvalidBaud));
+        assert_eq!(uart.divisor(), (54, 16));
+    }
+
+    static LIGHT: MockRegs = MockRegs::new();
+
+    #[test]
+    fn light_reconfigure_keeps_uart_enabled() {
+        // 发送器一直忙：走关闭 UART 的路径时会等待发送完成并清零 uartcr
+        LIGHT.set(UARTFR, FR_BUSY);
+        LIGHT.set(UARTCR, 0x301);
+        LIGHT.set(UARTLCRH, 0x70);
+        let uart = LIGHT.uart();
+        uart.set_fifo_levels(FifoLevel::OneEighth, FifoLevel::ThreeQuarters);
+        uart.set_interrupt_mask(INTERRUPT::RXIM::SET);
+        assert_eq!(LIGHT.get(UARTIFLS), 0b011_000);
+        assert_eq!(LIGHT.get(UARTIMSC), INT_RX);
+        // UART 保持使能，FEN 没有被清除，FIFO 中的数据不受影响
+        assert_eq!(LIGHT.get(UARTCR), 0x301);
+        assert_eq!(LIGHT.get(UARTLCRH), 0x70);
+    }
+
+    static CONTROL: MockRegs = MockRegs::new();
+
+    #[test]
+    fn control_round_trip() {
+        CONTROL.set(UARTFR, FR_TXFE);
+        let uart = CONTROL.uart();
+        let config = ControlConfig {
+            uart_enable: true,
+            tx_enable: true,
+            rx_enable: false,
+            rts: true,
+            loopback: true,
+            flow_control: FlowControl::Cts,
+        };
+        uart.set_control(config);
+        assert_eq!(uart.control(), config);
+        // UARTEN | TXE | LBE | RTS | CTSEN
+        assert_eq!(CONTROL.get(UARTCR), 0x1 | 0x100 | 0x80 | 0x800 | 0x8000);
+    }
+
+    static RS485: MockRegs = MockRegs::new();
+    static DE_ON_CR: AtomicU32 = AtomicU32::new(0);
+
+    fn record_de(on: bool) {
+        // 使能驱动器时记下控制寄存器，检查发送期间的 TXE/RXE
+        if on {
+            DE_ON_CR.store(RS485.get(UARTCR), Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn rs485_preset_sequences_tx_and_rx() {
+        RS485.set(UARTFR, FR_TXFE);
+        let mut uart = RS485.uart();
+        uart.configure_rs485(record_de, true);
+        // 空闲时只接收：RXE，无 TXE
+        assert_eq!(RS485.get(UARTCR) & 0x300, 0x200);
+        assert_eq!(spin_on::spin_on(uart.write_and_drain(b"ab")), 2);
+        // 发送期间只发送，回环字节不会进入接收 FIFO
+        assert_eq!(DE_ON_CR.load(Ordering::Relaxed) & 0x300, 0x100);
+        assert_eq!(RS485.get(UARTCR) & 0x300, 0x200);
+    }
+
+    static NOISY: MockRegs = MockRegs::new();
+    static NOISY_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_error(err: ReadError) {
+        assert_eq!(err, ReadError::Overrun);
+        NOISY_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn error_handler_read_keeps_going() {
+        // 只带 OE 的字节本身有效：每个字节报告一次错误，同时写入缓冲区
+        NOISY.set(UARTFR, 0);
+        NOISY.set(0, 1 << 11 | b'z' as u32);
+        let mut uart = NOISY.uart();
+        let mut buf = [0u8; 3];
+        let len = spin_on::spin_on(uart.read_with_error_handler(&mut buf, count_error));
+        assert_eq!(len, 3);
+        assert_eq!(buf, *b"zzz");
+        assert_eq!(NOISY_ERRORS.load(Ordering::Relaxed), 3);
+    }
+
+    static QUICK: MockRegs = MockRegs::new();
+
+    #[test]
+    fn quick_check_restores_state() {
+        for (i, id) in [0x11, 0x10, 0x34, 0x00].into_iter().enumerate() {
+            QUICK.set(0xfe0 + i * 4, id);
+        }
+        for (i, id) in [0x0d, 0xf0, 0x05, 0xb1].into_iter().enumerate() {
+            QUICK.set(0xff0 + i * 4, id);
+        }
+        QUICK.set(UARTFR, FR_TXFE);
+        let mut uart = QUICK.uart();
+        // 没有配置过波特率时无法做回环测试
+        assert_eq!(uart.quick_check(), Err(Error::NotEnabled));
+        uart.init(ClockHz(CLK_RATE), Baud(BAUD_RATE)).unwrap();
+        let ibrd = QUICK.get(UARTIBRD);
+        // 模拟寄存器读回写入 DR 的测试字节，相当于回环成功
+        let check = uart.quick_check().unwrap();
+        assert!(check.passed());
+        assert_eq!(QUICK.get(UARTCR), 0x301);
+        assert_eq!(QUICK.get(UARTIBRD), ibrd);
+    }
+
+    static HANDOFF: MockRegs = MockRegs::new();
+
+    #[test]
+    fn transfer_ownership_masks_interrupts() {
+        HANDOFF.set(UARTFR, FR_TXFE);
+        HANDOFF.set(UARTIMSC, INT_RX | INT_TX);
+        let uart = HANDOFF.uart();
+        assert_eq!(uart.transfer_ownership(), Ok(()));
+        assert_eq!(HANDOFF.get(UARTIMSC), 0);
+    }
+
+    #[cfg(feature = "embedded-io")]
+    static BUF_READER: MockRegs = MockRegs::new();
+
+    #[test]
+    fn buf_reader_fills_from_fifo() {
+        // 需要 `embedded-io` feature，未启用时测试为空
+        #[cfg(feature = "embedded-io")]
+        {
+            use embedded_io::BufRead;
+            // 模拟寄存器一直有数据，一次填满内部缓冲区
+            BUF_READER.set(UARTFR, 0);
+            BUF_READER.set(0, b'q' as u32);
+            let uart = BUF_READER.uart();
+            let mut reader = pl011::UartBufReader::<4>::new(&uart);
+            assert_eq!(reader.fill_buf().unwrap(), b"qqqq");
+            reader.consume(2);
+            // 之后到达带帧错误的字节：先交付缓冲区中剩下的数据，取完后才报告错误
+            BUF_READER.set(0, 1 << 8 | b'x' as u32);
+            assert_eq!(reader.fill_buf().unwrap(), b"qq");
+            reader.consume(2);
+            assert_eq!(reader.buffered(), 0);
+            assert_eq!(reader.fill_buf(), Err(ReadError::Framing));
+        }
+    }
+
+    static CB_WRITE: MockRegs = MockRegs::new();
+    static CB_WRITE_DONE: AtomicUsize = AtomicUsize::new(0);
+
+    fn cb_write_done(len: usize) {
+        CB_WRITE_DONE.store(len, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn write_async_cb_completes_after_drain() {
+        // FIFO 满：数据留待发送中断写入，期间占用 TXIM
+        CB_WRITE.set(UARTFR, FR_TXFF | FR_BUSY);
+        CB_WRITE.set(UARTIMSC, INT_RX);
+        let uart = CB_WRITE.uart();
+        assert!(uart.write_async_cb(b"hi", cb_write_done));
+        assert_eq!(CB_WRITE.get(UARTIMSC), INT_RX | INT_TX);
+        // FIFO 有空间后全部写入，最后一个字节还在移位寄存器中；不在调用者的上下文中回调，
+        // 数据全部进入 FIFO 后 TXIM 立即恢复
+        CB_WRITE.set(UARTFR, FR_BUSY);
+        CB_WRITE.set(UARTRIS, INT_TX);
+        uart.handle_interrupt();
+        assert_eq!(CB_WRITE.get(0), b'i' as u32);
+        assert_eq!(CB_WRITE_DONE.load(Ordering::Relaxed), 0);
+        assert_eq!(CB_WRITE.get(UARTIMSC), INT_RX);
+        // 发送器仍然忙：不回调，发送中断照常清除，不会持续触发
+        CB_WRITE.set(UARTRIS, INT_TX | INT_RX);
+        uart.handle_interrupt();
+        assert_eq!(CB_WRITE_DONE.load(Ordering::Relaxed), 0);
+        assert_eq!(CB_WRITE.get(UARTICR), INT_TX | INT_RX);
+        // 发送器空闲后，下一次（接收）中断中回调
+        CB_WRITE.set(UARTFR, FR_TXFE);
+        CB_WRITE.set(UARTRIS, INT_RX);
+        uart.handle_interrupt();
+        assert_eq!(CB_WRITE_DONE.load(Ordering::Relaxed), 2);
+        assert_eq!(CB_WRITE.get(UARTIMSC), INT_RX);
+        assert!(uart.prepare_for_idle());
+    }
+
+    static BUFFERED_DRAIN: MockRegs = MockRegs::new();
+    static BUFFERED_DRAIN_SPINS: AtomicUsize = AtomicUsize::new(0);
+
+    // 发送 FIFO 在第 2 次自旋后有空间，第 4 次后清空，移位寄存器在第 6 次后空闲
+    fn buffered_drain_step() {
+        // 软件队列和 FIFO 中的数据发完之前 UART 不能被关闭
+        assert_ne!(BUFFERED_DRAIN.get(UARTCR), 0);
+        let spins = BUFFERED_DRAIN_SPINS.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fr = BUFFERED_DRAIN.get(UARTFR);
+        if spins >= 2 {
+            fr &= !FR_TXFF;
+        }
+        if spins >= 4 {
+            fr |= FR_TXFE;
+        }
+        if spins >= 6 {
+            fr &= !FR_BUSY;
+        }
+        BUFFERED_DRAIN.set(UARTFR, fr);
+    }
+
+    #[test]
+    fn buffered_disable_drains_software_queue() {
+        BUFFERED_DRAIN.set(UARTFR, FR_TXFF | FR_BUSY);
+        BUFFERED_DRAIN.set(UARTCR, 0x301);
+        let mut uart = BUFFERED_DRAIN.uart();
+        uart.set_spin_hook(buffered_drain_step);
+        let buffered = BufferedUart::<8>::new(uart);
+        // 发送 FIFO 满，数据只能留在软件队列中
+        assert_eq!(buffered.write(b"bye"), 3);
+        assert_eq!(buffered.tx_free(), 5);
+        assert_eq!(buffered.disable(), Ok(()));
+        assert_eq!(buffered.tx_free(), 8);
+        assert_eq!(BUFFERED_DRAIN.get(0), b'e' as u32);
+        assert_eq!(BUFFERED_DRAIN_SPINS.load(Ordering::Relaxed), 6);
+        assert_eq!(BUFFERED_DRAIN.get(UARTCR), 0);
+    }
 }